@@ -1047,6 +1047,155 @@ extern "C" {
         error: *mut *mut hs_compile_error_t,
     ) -> hs_error_t;
 }
+extern "C" {
+    /// The basic pure literal expression compiler.
+    ///
+    /// This is the function call with which a pure literal expression (not a
+    /// common regular expression) is compiled into a Hyperscan database which
+    /// can be passed to the runtime functions (such as @ref hs_scan(),
+    /// @ref hs_open_stream(), etc.)
+    ///
+    /// @param expression
+    /// The NULL-terminated expression to parse. Note that this string must
+    /// represent ONLY the pattern to be matched, with no delimiters or flags;
+    /// any global flags should be specified with the @p flags argument. Unlike
+    /// @ref hs_compile(), the expression may contain NULL bytes, its length is
+    /// given by the @p len argument, and no character is treated as a special
+    /// regex syntax.
+    ///
+    /// @param flags
+    /// Flags which modify the behaviour of the expression. Multiple flags may
+    /// be used by ORing them together. Compared to @ref hs_compile(), fewer
+    /// valid values are provided:
+    /// - HS_FLAG_CASELESS - Matching will be performed case-insensitively.
+    /// - HS_FLAG_SINGLEMATCH - Only one match will be generated for the
+    /// expression per stream.
+    /// - HS_FLAG_SOM_LEFTMOST - Report the leftmost start of match offset
+    /// when a match is found.
+    ///
+    /// @param len
+    /// The length of the text content of the pure literal expression. As the
+    /// text content indicated by @p expression is treated as single character
+    /// one by one, the special terminating character `\0` should not
+    /// terminate the expression, the length must be given explicitly.
+    ///
+    /// @param mode
+    /// Compiler mode flags that affect the database as a whole. One of @ref
+    /// HS_MODE_STREAM or @ref HS_MODE_BLOCK or @ref HS_MODE_VECTORED must be
+    /// supplied, to select between the generation of a streaming, block or
+    /// vectored database. See @ref HS_MODE_FLAG for more details.
+    ///
+    /// @param platform
+    /// If not NULL, the platform structure is used to determine the target
+    /// platform for the database. If NULL, a database suitable for running
+    /// on the current host platform is produced.
+    ///
+    /// @param db
+    /// On success, a pointer to the generated database will be returned in
+    /// this parameter, or NULL on failure. The caller is responsible for
+    /// deallocating the buffer using the @ref hs_free_database() function.
+    ///
+    /// @param error
+    /// If the compile fails, a pointer to a @ref hs_compile_error_t will be
+    /// returned, providing details of the error condition. The caller is
+    /// responsible for deallocating the buffer using the @ref
+    /// hs_free_compile_error() function.
+    ///
+    /// @return
+    /// @ref HS_SUCCESS is returned on successful compilation; @ref
+    /// HS_COMPILER_ERROR on failure, with details provided in the error
+    /// parameter.
+    ///
+    #[link_name = "\u{1}_hs_compile_lit"]
+    pub fn hs_compile_lit(
+        expression: *const ::std::os::raw::c_char,
+        flags: ::std::os::raw::c_uint,
+        len: usize,
+        mode: ::std::os::raw::c_uint,
+        platform: *const hs_platform_info_t,
+        db: *mut *mut hs_database_t,
+        error: *mut *mut hs_compile_error_t,
+    ) -> hs_error_t;
+}
+extern "C" {
+    /// The multiple pure literal expression compiler.
+    ///
+    /// This is the function call with which a set of pure literal expressions
+    /// is compiled into a database which can be passed to the runtime functions
+    /// (such as @ref hs_scan(), @ref hs_open_stream(), etc.) Each expression
+    /// can be labelled with a unique integer which is passed into the match
+    /// callback to identify the pattern that has matched.
+    ///
+    /// @param expressions
+    /// The NULL-terminated expression to parse. Note that this string must
+    /// represent ONLY the pattern to be matched, with no delimiters or flags;
+    /// any global flags should be specified with the @p flags argument. The
+    /// expressions may contain NULL bytes, their lengths are given by the
+    /// @p lens array.
+    ///
+    /// @param flags
+    /// Array of flags which modify the behaviour of each expression. Multiple
+    /// flags may be used by ORing them together. Specifying the NULL pointer
+    /// in place of an array will set the flags value for all patterns to zero.
+    /// Compared to @ref hs_compile_multi(), fewer valid values are provided:
+    /// - HS_FLAG_CASELESS - Matching will be performed case-insensitively.
+    /// - HS_FLAG_SINGLEMATCH - Only one match will be generated for the
+    /// expression per stream.
+    /// - HS_FLAG_SOM_LEFTMOST - Report the leftmost start of match offset
+    /// when a match is found.
+    ///
+    /// @param ids
+    /// An array of integers specifying the ID number to be associated with the
+    /// corresponding pattern in the expressions array. Specifying the NULL
+    /// pointer in place of an array will set the ID value for all patterns to
+    /// zero.
+    ///
+    /// @param lens
+    /// Array of lengths of the text content of each pure literal expression.
+    ///
+    /// @param elements
+    /// The number of elements in the input arrays.
+    ///
+    /// @param mode
+    /// Compiler mode flags that affect the database as a whole. One of @ref
+    /// HS_MODE_STREAM or @ref HS_MODE_BLOCK or @ref HS_MODE_VECTORED must be
+    /// supplied, to select between the generation of a streaming, block or
+    /// vectored database. See @ref HS_MODE_FLAG for more details.
+    ///
+    /// @param platform
+    /// If not NULL, the platform structure is used to determine the target
+    /// platform for the database. If NULL, a database suitable for running
+    /// on the current host platform is produced.
+    ///
+    /// @param db
+    /// On success, a pointer to the generated database will be returned in
+    /// this parameter, or NULL on failure. The caller is responsible for
+    /// deallocating the buffer using the @ref hs_free_database() function.
+    ///
+    /// @param error
+    /// If the compile fails, a pointer to a @ref hs_compile_error_t will be
+    /// returned, providing details of the error condition. The caller is
+    /// responsible for deallocating the buffer using the @ref
+    /// hs_free_compile_error() function.
+    ///
+    /// @return
+    /// @ref HS_SUCCESS is returned on successful compilation; @ref
+    /// HS_COMPILER_ERROR on failure, with details provided in the error
+    /// parameter.
+    ///
+    #[link_name = "\u{1}_hs_compile_lit_multi"]
+    pub fn hs_compile_lit_multi(
+        expressions: *const *const ::std::os::raw::c_char,
+        flags: *const ::std::os::raw::c_uint,
+        ids: *const ::std::os::raw::c_uint,
+        lens: *const usize,
+        elements: ::std::os::raw::c_uint,
+        mode: ::std::os::raw::c_uint,
+        platform: *const hs_platform_info_t,
+        db: *mut *mut hs_database_t,
+        error: *mut *mut hs_compile_error_t,
+    ) -> hs_error_t;
+}
 extern "C" {
     /// Free an error structure generated by @ref hs_compile(), @ref
     /// hs_compile_multi() or @ref hs_compile_ext_multi().
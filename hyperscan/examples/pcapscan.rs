@@ -50,8 +50,8 @@ use pnet::packet::udp::UdpPacket;
 use pnet::packet::{Packet, PrimitiveValues};
 
 use hyperscan::{
-    BlockDatabase, BlockScanner, Database, DatabaseBuilder, Pattern, Patterns, RawScratch, RawStream, Scratch,
-    ScratchAllocator, Stream, StreamingDatabase, StreamingScanner,
+    BlockDatabase, BlockScanner, Database, DatabaseBuilder, Match, Matching, Pattern, Patterns, RawScratch, Scratch,
+    ScratchAllocator, StreamTable, StreamingDatabase,
 };
 
 #[derive(Debug)]
@@ -175,7 +175,7 @@ impl FiveTuple {
 
 const IP_FLAG_MF: u8 = 1;
 
-struct Benchmark {
+struct Benchmark<'a> {
     /// Packet data to be scanned.
     packets: Vec<Box<Vec<u8>>>,
 
@@ -186,26 +186,29 @@ struct Benchmark {
     stream_map: HashMap<FiveTuple, usize>,
 
     /// Hyperscan compiled database (streaming mode)
-    db_streaming: StreamingDatabase,
+    db_streaming: &'a StreamingDatabase,
 
     /// Hyperscan compiled database (block mode)
-    db_block: BlockDatabase,
+    db_block: &'a BlockDatabase,
 
     /// Hyperscan temporary scratch space (used in both modes)
     scratch: RawScratch,
 
-    // Vector of Hyperscan stream state (used in streaming mode)
-    streams: Vec<RawStream>,
+    // Table of Hyperscan stream state keyed by stream ID (used in streaming mode)
+    streams: StreamTable<'a, usize>,
 
     // Count of matches found during scanning
     match_count: AtomicUsize,
 }
 
-impl Benchmark {
-    fn new(db_streaming: StreamingDatabase, db_block: BlockDatabase) -> Result<Benchmark, hyperscan::Error> {
+impl<'a> Benchmark<'a> {
+    fn new(
+        db_streaming: &'a StreamingDatabase,
+        db_block: &'a BlockDatabase,
+    ) -> Result<Benchmark<'a>, hyperscan::Error> {
         let mut s = try!(db_streaming.alloc());
 
-        try!(s.realloc(&db_block));
+        try!(s.realloc(db_block));
 
         Ok(Benchmark {
             packets: Vec::new(),
@@ -214,7 +217,7 @@ impl Benchmark {
             db_streaming: db_streaming,
             db_block: db_block,
             scratch: s,
-            streams: Vec::new(),
+            streams: StreamTable::new(db_streaming),
             match_count: AtomicUsize::new(0),
         })
     }
@@ -294,35 +297,50 @@ impl Benchmark {
         self.match_count.store(0, Ordering::Relaxed);
     }
 
-    fn on_match(_: u32, _: u64, _: u64, _: u32, match_count: &AtomicUsize) -> u32 {
-        match_count.fetch_add(1, Ordering::Relaxed);
+    fn on_match<'b>(match_count: &'b AtomicUsize) -> impl FnMut(Match) -> Matching + 'b {
+        move |_| {
+            match_count.fetch_add(1, Ordering::Relaxed);
 
-        0
+            Matching::Continue
+        }
+    }
+
+    fn on_stream_match<'b>(match_count: &'b AtomicUsize) -> impl FnMut(&usize, Match) -> Matching + 'b {
+        move |_, _| {
+            match_count.fetch_add(1, Ordering::Relaxed);
+
+            Matching::Continue
+        }
     }
 
     // Open a Hyperscan stream for each stream in stream_ids
     fn open_streams(&mut self) {
-        self.streams = self
-            .stream_map
-            .iter()
-            .map(|_| self.db_streaming.open_stream(0).unwrap())
-            .collect()
+        for id in 0..self.stream_map.len() {
+            if let Err(err) = self
+                .streams
+                .open(id, &self.scratch, Self::on_stream_match(&self.match_count))
+            {
+                println!("ERROR: Unable to open stream. Exiting. {}", err);
+            }
+        }
     }
 
     // Close all open Hyperscan streams (potentially generating any end-anchored matches)
     fn close_streams(&mut self) {
-        for ref stream in &self.streams {
-            if let Err(err) = stream.close(&self.scratch, Some(Self::on_match), Some(&self.match_count)) {
-                println!("ERROR: Unable to close stream. Exiting. {}", err);
-            }
+        if let Err(err) = self
+            .streams
+            .close_all(&self.scratch, Self::on_stream_match(&self.match_count))
+        {
+            println!("ERROR: Unable to close stream. Exiting. {}", err);
         }
     }
 
     fn reset_streams(&mut self) {
-        for ref stream in &self.streams {
-            if let Err(err) = stream.reset(0, &self.scratch, Some(Self::on_match), Some(&self.match_count)) {
-                println!("ERROR: Unable to reset stream. Exiting. {}", err);
-            }
+        if let Err(err) = self
+            .streams
+            .reset_all(&self.scratch, Self::on_stream_match(&self.match_count))
+        {
+            println!("ERROR: Unable to reset stream. Exiting. {}", err);
         }
     }
 
@@ -330,14 +348,11 @@ impl Benchmark {
     // through Hyperscan using the streaming interface.
     fn scan_streams(&mut self) {
         for (i, ref packet) in self.packets.iter().enumerate() {
-            let ref stream = self.streams[self.stream_ids[i]];
-
-            if let Err(err) = stream.scan(
+            if let Err(err) = self.streams.scan(
+                self.stream_ids[i],
                 packet.as_ref().as_slice(),
-                0,
                 &self.scratch,
-                Some(Self::on_match),
-                Some(&self.match_count),
+                Self::on_stream_match(&self.match_count),
             ) {
                 println!("ERROR: Unable to scan packet. Exiting. {}", err)
             }
@@ -352,8 +367,7 @@ impl Benchmark {
                 packet.as_ref().as_slice(),
                 0,
                 &self.scratch,
-                Some(Self::on_match),
-                Some(&self.match_count),
+                Self::on_match(&self.match_count),
             ) {
                 println!("ERROR: Unable to scan packet. Exiting. {}", err)
             }
@@ -463,7 +477,7 @@ fn main() {
     };
 
     // Read our input PCAP file in
-    let mut bench = Benchmark::new(db_streaming, db_block).unwrap();
+    let mut bench = Benchmark::new(&db_streaming, &db_block).unwrap();
 
     println!("PCAP input file: {}", pcap_file);
 
@@ -109,9 +109,8 @@ fn main() {
     // asynchronous APIs, Hyperscan operates synchronously: all matches will be
     // found, and all callbacks issued, *before* hs_scan returns.
     //
-    // In this example, we provide the input pattern as the context pointer so
-    // that the callback is able to print out the pattern that matched on each
-    // match event.
+    // In this example, the callback closure captures the input pattern so that
+    // it is able to print out the pattern that matched on each match event.
     //
 
     let scratch = match database.alloc() {
@@ -125,13 +124,13 @@ fn main() {
     println!("Scanning {} bytes with Hyperscan", input_data.len());
 
     // This is the function that will be called for each match that occurs.
-    fn event_handler(_: u32, _: u64, to: u64, _: u32, pattern: &hyperscan::Pattern) -> u32 {
-        println!("Match for pattern \"{}\" at offset {}", &pattern, to);
+    let event_handler = |m: Match| {
+        println!("Match for pattern \"{}\" at offset {}", &pattern, m.to);
 
-        0
+        Matching::Continue
     };
 
-    if let Err(err) = database.scan(input_data.as_str(), 0, &scratch, Some(event_handler), Some(&pattern)) {
+    if let Err(err) = database.scan(input_data.as_str(), 0, &scratch, event_handler) {
         write!(io::stderr(), "ERROR: Unable to scan input buffer. Exiting. {}\n", err);
         exit(-1);
     }
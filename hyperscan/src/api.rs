@@ -5,11 +5,13 @@ use std::mem;
 use std::ops::Deref;
 use std::os::raw::c_char;
 use std::ptr;
+use std::str::FromStr;
 
 use libc;
 
 use constants::*;
 use errors::Error;
+use raw;
 use raw::*;
 
 /// Compile mode
@@ -81,7 +83,7 @@ pub trait Database: Deref<Target = RawDatabasePtr> {
     fn database_size(&self) -> Result<usize, Error>;
 
     /// Utility function providing information about a database.
-    fn database_info(&self) -> Result<String, Error>;
+    fn database_info(&self) -> Result<DatabaseInfo, Error>;
 }
 
 /// A pattern database can be serialized to a stream of bytes.
@@ -94,8 +96,10 @@ pub trait SerializableDatabase<T: Database, S: SerializedDatabase>: Database {
     fn deserialize(bytes: &[u8]) -> Result<T, Error>;
 
     /// Reconstruct a pattern database from a stream of bytes
-    /// previously generated by RawDatabase::serialize() at a given memory location.
-    fn deserialize_at(&self, bytes: &[u8]) -> Result<&T, Error>;
+    /// previously generated by RawDatabase::serialize() at the memory location of the given database.
+    ///
+    /// Fails with `Error::InsufficientSpace` if the database is smaller than the deserialized one.
+    fn deserialize_at(&mut self, bytes: &[u8]) -> Result<&T, Error>;
 }
 
 /// A pattern database was serialized to a stream of bytes.
@@ -126,7 +130,7 @@ pub trait SerializedDatabase {
         Ok(size)
     }
 
-    fn database_info(&self) -> Result<String, Error> {
+    fn database_info(&self) -> Result<DatabaseInfo, Error> {
         let mut p: *mut c_char = ptr::null_mut();
 
         unsafe {
@@ -137,7 +141,7 @@ pub trait SerializedDatabase {
             ));
 
             let result = match CStr::from_ptr(p).to_str() {
-                Ok(info) => Ok(info.to_string()),
+                Ok(info) => info.parse(),
                 Err(_) => Err(Error::Invalid),
             };
 
@@ -146,6 +150,143 @@ pub trait SerializedDatabase {
             result
         }
     }
+
+    /// Provides the compiled mode of the serialized database.
+    fn database_mode(&self) -> Result<u32, Error> {
+        self.database_info().map(|info| info.mode)
+    }
+}
+
+/// The version of Hyperscan.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    /// The major version number.
+    pub major: u8,
+    /// The minor version number.
+    pub minor: u8,
+    /// The release number.
+    pub release: u8,
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.release)
+    }
+}
+
+impl FromStr for Version {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(3, '.');
+        let mut next = || parts.next().ok_or(Error::Invalid);
+
+        Ok(Version {
+            major: try!(try!(next()).parse()),
+            minor: try!(try!(next()).parse()),
+            release: try!(try!(next()).parse()),
+        })
+    }
+}
+
+/// The version of the linked Hyperscan library.
+///
+/// Returns `Error::Invalid` if the library reports a version string which can't be parsed.
+pub fn hs_version() -> Result<Version, Error> {
+    let version = unsafe { CStr::from_ptr(raw::hs_version()) }.to_string_lossy();
+
+    version
+        .split_whitespace()
+        .next()
+        .ok_or(Error::Invalid)
+        .and_then(str::parse)
+}
+
+/// The information about a compiled pattern database.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DatabaseInfo {
+    /// The version of Hyperscan which compiled the database.
+    pub version: Version,
+
+    /// The CPU features which the database was compiled for, as `HS_CPU_FEATURES_*` flags.
+    pub cpu_features: u64,
+
+    /// The mode which the database was compiled for, as `HS_MODE_*` flag.
+    pub mode: u32,
+}
+
+impl DatabaseInfo {
+    /// Whether the database was compiled for the AVX2 instruction set.
+    pub fn has_avx2(&self) -> bool {
+        self.cpu_features & u64::from(HS_CPU_FEATURES_AVX2) != 0
+    }
+
+    /// Whether the database was compiled for the AVX512 instruction set.
+    pub fn has_avx512(&self) -> bool {
+        self.cpu_features & u64::from(HS_CPU_FEATURES_AVX512) != 0
+    }
+
+    /// Whether the platform supports all the CPU features which the database was compiled for.
+    pub fn is_supported_by(&self, platform: &PlatformInfo) -> bool {
+        self.cpu_features & !platform.cpu_features() == 0
+    }
+
+    /// The name of the mode which the database was compiled for.
+    pub fn mode_name(&self) -> &'static str {
+        match self.mode {
+            HS_MODE_STREAM => "STREAM",
+            HS_MODE_VECTORED => "VECTORED",
+            _ => "BLOCK",
+        }
+    }
+}
+
+impl fmt::Display for DatabaseInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Version: {} Features: {} {} Mode: {}",
+            self.version,
+            if self.has_avx2() { "AVX2" } else { "" },
+            if self.has_avx512() { "AVX512" } else { "" },
+            self.mode_name()
+        )
+    }
+}
+
+impl FromStr for DatabaseInfo {
+    type Err = Error;
+
+    /// Parse the information in the `Version: X Features: Y Mode: Z` format reported by Hyperscan.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut version = None;
+        let mut cpu_features = 0;
+        let mut mode = None;
+        let mut key = "";
+
+        for token in s.split_whitespace() {
+            match (key, token) {
+                (_, "Version:") | (_, "Features:") | (_, "Mode:") => key = token,
+                ("Version:", _) => version = Some(try!(token.parse())),
+                ("Features:", "AVX2") => cpu_features |= u64::from(HS_CPU_FEATURES_AVX2),
+                ("Features:", "AVX512") => cpu_features |= u64::from(HS_CPU_FEATURES_AVX512),
+                ("Features:", _) => {}
+                ("Mode:", "BLOCK") => mode = Some(HS_MODE_BLOCK),
+                ("Mode:", "STREAM") => mode = Some(HS_MODE_STREAM),
+                ("Mode:", "VECTORED") => mode = Some(HS_MODE_VECTORED),
+                _ => return Err(Error::Invalid),
+            }
+        }
+
+        match (version, mode) {
+            (Some(version), Some(mode)) => Ok(DatabaseInfo {
+                version,
+                cpu_features,
+                mode,
+            }),
+            _ => Err(Error::Invalid),
+        }
+    }
 }
 
 /// A type containing information on the target platform
@@ -189,6 +330,22 @@ impl PlatformInfo {
         })))
     }
 
+    /// The tuning of the platform, or of the host for the null platform.
+    pub fn tune(&self) -> u32 {
+        match self.0 {
+            Some(ref info) => info.borrow().tune,
+            None => PlatformInfo::host().tune(),
+        }
+    }
+
+    /// The CPU features of the platform, or of the host for the null platform.
+    pub fn cpu_features(&self) -> u64 {
+        match self.0 {
+            Some(ref info) => info.borrow().cpu_features,
+            None => PlatformInfo::host().cpu_features(),
+        }
+    }
+
     pub fn as_ptr(&self) -> RawPlatformInfoPtr {
         match self.0 {
             Some(ref info) => &*info.borrow(),
@@ -209,7 +366,8 @@ pub trait DatabaseBuilder<D: Database> {
 }
 
 /// A type containing information related to an expression
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ExpressionInfo {
     /// The minimum length in bytes of a match for the pattern.
     pub min_width: usize,
@@ -295,14 +453,45 @@ impl<'a> Scannable for &'a Vec<u8> {
 /// Flags modifying the behaviour of scan function
 pub type ScanFlags = u32;
 
+/// A match event reported by the scanner.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Match {
+    /// The ID number of the expression that matched.
+    pub id: u32,
+    /// The offset of the first byte that matches the expression,
+    /// only accurate when `HS_FLAG_SOM_LEFTMOST` is used.
+    pub from: u64,
+    /// The offset after the last byte that matches the expression.
+    pub to: u64,
+    /// This is provided for future use and is unused at present.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub flags: u32,
+}
+
+/// Indicating whether or not matching should continue on the target data.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Matching {
+    /// The matching should continue.
+    Continue,
+    /// The matching should cease.
+    Terminate,
+}
+
+impl Default for Matching {
+    #[inline]
+    fn default() -> Self {
+        Matching::Continue
+    }
+}
+
 /// Definition of the match event callback function type.
 ///
 /// This callback function will be invoked whenever a match is located in the
 /// target data during the execution of a scan. The details of the match are
 /// passed in as parameters to the callback function, and the callback function
 /// should return a value indicating whether or not matching should continue on
-/// the target data. If no callbacks are desired from a scan call, NULL may be
-/// provided in order to suppress match production.
+/// the target data.
 ///
 /// This callback function should not attempt to call Hyperscan API functions on
 /// the same stream nor should it attempt to reuse the scratch space allocated
@@ -312,54 +501,31 @@ pub type ScanFlags = u32;
 /// space), but reusing data structures like stream state and/or scratch space
 /// will produce undefined behavior.
 ///
-/// Fn(id: u32, from: u64, to: u64, flags: u32) -> bool
+/// If the callback function panics, the scan is terminated and the panic
+/// is resumed after the scan function returned.
 ///
-pub type MatchEventCallback<D> = fn(id: u32, from: u64, to: u64, flags: u32, data: &D) -> u32;
-pub type MatchEventCallbackMut<D> = fn(id: u32, from: u64, to: u64, flags: u32, data: &mut D) -> u32;
+/// FnMut(Match) -> Matching
+///
+pub trait MatchEventHandler: FnMut(Match) -> Matching {}
+
+impl<F> MatchEventHandler for F where F: FnMut(Match) -> Matching {}
 
 /// The block (non-streaming) regular expression scanner.
 pub trait BlockScanner<T: Scannable, S: Scratch> {
     /// This is the function call in which the actual pattern matching
     /// takes place for block-mode pattern databases.
-    fn scan<D>(
-        &self,
-        data: T,
-        flags: ScanFlags,
-        scratch: &S,
-        callback: Option<MatchEventCallback<D>>,
-        context: Option<&D>,
-    ) -> Result<&Self, Error>;
-
-    fn scan_mut<D>(
-        &mut self,
-        data: T,
-        flags: ScanFlags,
-        scratch: &S,
-        callback: Option<MatchEventCallbackMut<D>>,
-        context: Option<&mut D>,
-    ) -> Result<&Self, Error> {
-        self.scan(
-            data,
-            flags,
-            scratch,
-            callback.map(|f| unsafe { mem::transmute::<MatchEventCallbackMut<D>, MatchEventCallback<D>>(f) }),
-            context.map(|v| &*v),
-        )
-    }
+    fn scan<F>(&self, data: T, flags: ScanFlags, scratch: &S, on_match: F) -> Result<&Self, Error>
+    where
+        F: MatchEventHandler;
 }
 
 /// The vectored regular expression scanner.
 pub trait VectoredScanner<T: Scannable, S: Scratch> {
     /// This is the function call in which the actual pattern matching
     /// takes place for vectoring-mode pattern databases.
-    fn scan<D>(
-        &self,
-        data: &[T],
-        flags: ScanFlags,
-        scratch: &S,
-        callback: Option<MatchEventCallback<D>>,
-        context: Option<&D>,
-    ) -> Result<&Self, Error>;
+    fn scan<F>(&self, data: &[T], flags: ScanFlags, scratch: &S, on_match: F) -> Result<&Self, Error>
+    where
+        F: MatchEventHandler;
 }
 
 /// Raw `Stream` pointer
@@ -369,31 +535,27 @@ pub type RawStreamPtr = *mut hs_stream_t;
 pub type StreamFlags = u32;
 
 /// The stream returned by StreamingDatabase::open_stream
+///
+/// The stream is closed without reporting any matches when it is dropped.
 pub trait Stream<S: Scratch>: Deref<Target = RawStreamPtr> {
-    /// Close a stream.
-    fn close<D>(
-        &self,
-        scratch: &S,
-        callback: Option<MatchEventCallback<D>>,
-        context: Option<&D>,
-    ) -> Result<&Self, Error>;
+    /// Close a stream, reporting any matches at end of data (EOD).
+    fn close<F>(self, scratch: &S, on_match: F) -> Result<(), Error>
+    where
+        Self: Sized,
+        F: MatchEventHandler;
 
     /// Reset a stream to an initial state.
-    fn reset<D>(
-        &self,
-        flags: StreamFlags,
-        scratch: &S,
-        callback: Option<MatchEventCallback<D>>,
-        context: Option<&D>,
-    ) -> Result<&Self, Error>;
+    fn reset<F>(&self, flags: StreamFlags, scratch: &S, on_match: F) -> Result<&Self, Error>
+    where
+        F: MatchEventHandler;
 }
 
 /// The streaming regular expression scanner.
-pub trait StreamingScanner<T, S>
+pub trait StreamingScanner<'a, T, S>
 where
-    T: Stream<S>,
+    T: Stream<S> + 'a,
     S: Scratch,
 {
-    /// Open and initialise a stream.
-    fn open_stream(&self, flags: StreamFlags) -> Result<T, Error>;
+    /// Open and initialise a stream, which can't outlive the database.
+    fn open_stream(&'a self, flags: StreamFlags) -> Result<T, Error>;
 }
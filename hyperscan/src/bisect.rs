@@ -0,0 +1,240 @@
+use api::*;
+use common::RawDatabase;
+use compile::{Pattern, Patterns};
+use errors::Error;
+
+/// A pattern which is responsible for a compile failure.
+#[derive(Debug, Clone)]
+pub struct Culprit {
+    /// The pattern in the minimal failing subset.
+    pub pattern: Pattern,
+    /// The information of the pattern, if it could be analyzed on its own.
+    pub info: Option<ExpressionInfo>,
+}
+
+/// The result of bisecting a compile failure.
+#[derive(Debug, Clone)]
+pub struct BisectReport {
+    /// The compile error of the whole pattern set.
+    pub error: Error,
+    /// The patterns of a minimal subset which still fails to compile.
+    pub culprits: Vec<Culprit>,
+    /// The number of compiles which were tried.
+    pub compiles: usize,
+}
+
+/// Find the patterns which are responsible for a compile failure.
+pub trait Bisect {
+    /// Compile the patterns in the mode of `T`, and bisect the failure if it fails.
+    ///
+    /// A database-wide compile error, such as a resource limit, doesn't tell which expression causes it,
+    /// so the subsets of the patterns are compiled repeatedly (delta debugging) to find
+    /// a minimal subset which still fails with the same database-wide error.
+    /// An error of a specific expression reports that expression without bisecting.
+    ///
+    /// Returns `None` if the patterns compile successfully.
+    fn bisect<T: Type>(&self, platform: &PlatformInfo) -> Option<BisectReport>;
+}
+
+impl Bisect for Patterns {
+    fn bisect<T: Type>(&self, platform: &PlatformInfo) -> Option<BisectReport> {
+        let mut compiles = 1;
+
+        let res: Result<RawDatabase<T>, Error> = self.build_for_platform(platform);
+
+        let error = match res {
+            Ok(_) => return None,
+            Err(err) => err,
+        };
+
+        let failing = match error {
+            Error::ExpressionError(ref err) => self.get(err.index).into_iter().cloned().collect(),
+            Error::CompilerError(ref reason) => ddmin(self, |subset| {
+                compiles += 1;
+
+                let res: Result<RawDatabase<T>, Error> = subset.to_vec().build_for_platform(platform);
+
+                // only the same failure reproduces it, another database-wide error may have other culprits
+                match res {
+                    Err(Error::CompilerError(ref err)) => err == reason,
+                    _ => false,
+                }
+            }),
+            _ => vec![],
+        };
+
+        let culprits = failing
+            .into_iter()
+            .map(|pattern| Culprit {
+                info: pattern.info().ok(),
+                pattern,
+            })
+            .collect::<Vec<_>>();
+
+        debug!(
+            "bisected {} patterns to {} culprits in {} compiles, {}",
+            self.len(),
+            culprits.len(),
+            compiles,
+            error
+        );
+
+        Some(BisectReport {
+            error,
+            culprits,
+            compiles,
+        })
+    }
+}
+
+/// Find a minimal subset of the patterns which still fails with the delta debugging algorithm.
+///
+/// The result is 1-minimal, removing any single pattern from it makes it pass.
+fn ddmin<F>(patterns: &[Pattern], mut fails: F) -> Vec<Pattern>
+where
+    F: FnMut(&[Pattern]) -> bool,
+{
+    let mut failing = patterns.to_vec();
+    let mut n = 2;
+
+    while failing.len() >= 2 {
+        let chunk_size = (failing.len() + n - 1) / n;
+        let subsets = failing.chunks(chunk_size).map(|s| s.to_vec()).collect::<Vec<_>>();
+        let mut reduced = None;
+
+        for (i, subset) in subsets.iter().enumerate() {
+            if fails(subset) {
+                reduced = Some((subset.clone(), 2));
+                break;
+            }
+
+            if subsets.len() > 2 {
+                let complement = subsets
+                    .iter()
+                    .enumerate()
+                    .filter(|&(j, _)| j != i)
+                    .flat_map(|(_, s)| s.iter().cloned())
+                    .collect::<Vec<_>>();
+
+                if fails(&complement) {
+                    reduced = Some((complement, (n - 1).max(2)));
+                    break;
+                }
+            }
+        }
+
+        match reduced {
+            Some((subset, granularity)) => {
+                failing = subset;
+                n = granularity;
+            }
+            None if n >= failing.len() => break,
+            None => n = (n * 2).min(failing.len()),
+        }
+    }
+
+    failing
+}
+
+#[cfg(test)]
+pub mod tests {
+    extern crate env_logger;
+
+    use super::super::*;
+    use super::ddmin;
+
+    #[test]
+    fn test_ddmin() {
+        let _ = env_logger::try_init();
+
+        let patterns = (1..20)
+            .map(|id| pattern! {format!("pattern{}", id), flags => 0, id => id})
+            .collect::<Vec<_>>();
+
+        // fails only when both pattern 3 and 17 are present
+        let mut tries = 0;
+        let failing = ddmin(&patterns, |subset| {
+            tries += 1;
+
+            subset.iter().any(|p| p.id == 3) && subset.iter().any(|p| p.id == 17)
+        });
+
+        assert_eq!(failing.iter().map(|p| p.id).collect::<Vec<_>>(), vec![3, 17]);
+        assert!(tries < patterns.len() * 4);
+    }
+
+    #[test]
+    fn test_ddmin_database() {
+        let _ = env_logger::try_init();
+
+        let mut patterns = (1..12)
+            .map(|id| pattern! {format!("pattern{}", id), flags => 0, id => id})
+            .collect::<Vec<_>>();
+
+        patterns.insert(6, pattern! {"3 & 7", flags => HS_FLAG_COMBINATION, id => 20});
+
+        // fails when the combination is reported, which needs both of its sub-patterns to compile
+        let failing = ddmin(&patterns, |subset| {
+            let db: BlockDatabase = match subset.to_vec().build() {
+                Ok(db) => db,
+                Err(_) => return false,
+            };
+            let s = db.alloc().unwrap();
+            let mut reported = false;
+
+            db.scan("pattern3 pattern7", 0, &s, |m: Match| {
+                reported |= m.id == 20;
+
+                Matching::Continue
+            })
+            .unwrap();
+
+            reported
+        });
+
+        assert_eq!(failing.iter().map(|p| p.id).collect::<Vec<_>>(), vec![3, 20, 7]);
+    }
+
+    #[test]
+    fn test_bisect() {
+        let _ = env_logger::try_init();
+
+        assert!(patterns!(["foo", "bar"])
+            .bisect::<Block>(&PlatformInfo::null())
+            .is_none());
+
+        let report = patterns!(["foo", "bar(", "baz"])
+            .bisect::<Block>(&PlatformInfo::null())
+            .unwrap();
+
+        match report.error {
+            Error::ExpressionError(ref err) => assert_eq!(err.index, 1),
+            ref err => panic!("unexpected error: {}", err),
+        }
+
+        assert_eq!(report.compiles, 1);
+        assert_eq!(report.culprits.len(), 1);
+        assert_eq!(report.culprits[0].pattern.id, 2);
+        assert!(report.culprits[0].info.is_none());
+    }
+
+    #[test]
+    fn test_bisect_compiler_error() {
+        let _ = env_logger::try_init();
+
+        // an invalid platform fails the whole database, whichever patterns it has
+        let platform = PlatformInfo::new(u32::max_value(), u64::max_value());
+        let report = patterns!(["foo", "bar", "baz", "qux"])
+            .bisect::<Block>(&platform)
+            .unwrap();
+
+        match report.error {
+            Error::CompilerError(_) => {}
+            ref err => panic!("unexpected error: {}", err),
+        }
+
+        assert!(report.compiles > 1);
+        assert_eq!(report.culprits.len(), 1);
+        assert!(report.culprits[0].info.is_some());
+    }
+}
@@ -0,0 +1,284 @@
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use sha2::{Digest, Sha256};
+
+use api::*;
+use common::RawDatabase;
+use compile::Patterns;
+use envelope::Envelope;
+use errors::Error;
+
+/// The extension of the cached database files.
+const CACHE_FILE_EXT: &str = "hsdb";
+
+/// The sequence number of the temporary files, which makes them unique among the threads of the process.
+static TMP_FILE_SEQ: AtomicUsize = AtomicUsize::new(0);
+
+/// The statistics of a database cache.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct CacheStats {
+    /// The number of databases loaded from the cache.
+    pub hits: usize,
+    /// The number of databases compiled because they were missing or invalid in the cache.
+    pub misses: usize,
+}
+
+/// An on-disk cache of compiled pattern databases.
+///
+/// The databases are stored in a directory, keyed by a hash of the patterns, their flags
+/// and extended parameters, the mode, the platform and the version of Hyperscan.
+/// The cached database is verified with a checksum, and its version, mode and platform
+/// are validated when it is loaded, otherwise the patterns are compiled and the cache is overwritten.
+#[derive(Debug)]
+pub struct DatabaseCache {
+    dir: PathBuf,
+    envelope: Envelope,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl DatabaseCache {
+    /// Constructs a cache which stores the databases in the directory.
+    pub fn new<P: AsRef<Path>>(dir: P) -> DatabaseCache {
+        DatabaseCache {
+            dir: dir.as_ref().to_path_buf(),
+            envelope: Envelope::new(),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    /// Sign the cached databases with the key, and only load the databases with a valid signature.
+    pub fn with_key<K: AsRef<[u8]>>(mut self, key: K) -> Self {
+        self.envelope = Envelope::with_key(key);
+        self
+    }
+
+    /// The directory which stores the databases.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The statistics of the cache.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// The cache key of the patterns compiled in the mode of `T` for the platform.
+    ///
+    /// It fails if the version of Hyperscan, which is part of the key, is unknown.
+    pub fn key<T: Type>(patterns: &Patterns, platform: &PlatformInfo) -> Result<String, Error> {
+        let version = try!(hs_version());
+        let mut hasher = Sha256::new();
+
+        hasher.input(version.to_string().as_bytes());
+        hasher.input(T::mode().to_le_bytes());
+        hasher.input(platform.tune().to_le_bytes());
+        hasher.input(platform.cpu_features().to_le_bytes());
+
+        for pattern in patterns {
+            hasher.input((pattern.id as u64).to_le_bytes());
+            hasher.input(pattern.flags.0.to_le_bytes());
+            hasher.input((pattern.expression.len() as u64).to_le_bytes());
+            hasher.input(pattern.expression.as_bytes());
+
+            for param in &[pattern.min_offset, pattern.max_offset, pattern.min_length] {
+                hasher.input(param.map_or(u64::max_value(), |n| n).to_le_bytes());
+            }
+            for param in &[pattern.edit_distance, pattern.hamming_distance] {
+                hasher.input(param.map_or(u64::max_value(), u64::from).to_le_bytes());
+            }
+        }
+
+        Ok(hasher.result().iter().fold(String::new(), |mut key, b| {
+            let _ = write!(key, "{:02x}", b);
+            key
+        }))
+    }
+
+    /// The path of the cached database file.
+    pub fn path<T: Type>(&self, patterns: &Patterns, platform: &PlatformInfo) -> Result<PathBuf, Error> {
+        Self::key::<T>(patterns, platform).map(|key| self.dir.join(key).with_extension(CACHE_FILE_EXT))
+    }
+
+    /// Load the database of the patterns from the cache, or compile and cache it on a miss.
+    ///
+    /// Failing to read or write the cache is not an error, the patterns are compiled instead.
+    pub fn load<T: Type>(&self, patterns: &Patterns, platform: &PlatformInfo) -> Result<RawDatabase<T>, Error> {
+        let path = try!(self.path::<T>(patterns, platform));
+
+        match self.load_cached(&path, platform) {
+            Ok(db) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+
+                debug!("load {} database from cache {:?}", T::name(), path);
+
+                return Ok(db);
+            }
+            Err(err) => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+
+                debug!("compile {} database, cache {:?} missed, {}", T::name(), path, err);
+            }
+        }
+
+        let db: RawDatabase<T> = try!(patterns.build_for_platform(platform));
+
+        match self.store(&path, &db) {
+            Ok(()) => debug!("cache {} database to {:?}", T::name(), path),
+            Err(err) => warn!("fail to cache {} database to {:?}, {}", T::name(), path, err),
+        }
+
+        Ok(db)
+    }
+
+    fn load_cached<T: Type>(&self, path: &Path, platform: &PlatformInfo) -> Result<RawDatabase<T>, String> {
+        let bytes = try!(fs::read(path).map_err(|err| err.to_string()));
+        let data = try!(self.envelope.open(&bytes).map_err(|err| err.to_string()));
+        let info = try!(data.database_info().map_err(|err| err.to_string()));
+
+        if info.version != try!(hs_version().map_err(|err| err.to_string())) {
+            return Err(format!("version {} mismatch", info.version));
+        }
+        if info.mode != T::mode() {
+            return Err(format!("mode {} mismatch", info.mode_name()));
+        }
+        if !info.is_supported_by(platform) {
+            return Err(format!("platform features {:#x} unsupported", info.cpu_features));
+        }
+
+        RawDatabase::deserialize(data).map_err(|err| err.to_string())
+    }
+
+    fn store<T: Type>(&self, path: &Path, db: &RawDatabase<T>) -> io::Result<()> {
+        let bytes = try!(self
+            .envelope
+            .seal_database(db)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err)));
+
+        try!(fs::create_dir_all(&self.dir));
+
+        // write to a temporary file first, so the readers never see a partial file
+        let tmp = path.with_extension(format!(
+            "{}.{}.{}",
+            CACHE_FILE_EXT,
+            process::id(),
+            TMP_FILE_SEQ.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        {
+            let mut f = try!(fs::OpenOptions::new().write(true).create_new(true).open(&tmp));
+
+            try!(f.write_all(&bytes));
+            try!(f.sync_all());
+        }
+
+        if let Err(err) = fs::rename(&tmp, path) {
+            let _ = fs::remove_file(&tmp);
+
+            return Err(err);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    extern crate env_logger;
+
+    use std::env;
+    use std::fs;
+    use std::process;
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::super::*;
+
+    #[test]
+    fn test_database_cache() {
+        let _ = env_logger::try_init();
+
+        let dir = env::temp_dir().join(format!("hyperscan-cache-{}", process::id()));
+        let cache = DatabaseCache::new(&dir);
+        let patterns = patterns!(["foo", "bar"]);
+        let platform = PlatformInfo::host();
+
+        let db: BlockDatabase = cache.load(&patterns, &platform).unwrap();
+
+        assert_eq!(cache.stats(), CacheStats { hits: 0, misses: 1 });
+
+        let path = cache.path::<Block>(&patterns, &platform).unwrap();
+
+        assert!(path.exists());
+
+        let cached: BlockDatabase = cache.load(&patterns, &platform).unwrap();
+
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 1 });
+        assert_eq!(
+            cached.serialize().unwrap().as_slice(),
+            db.serialize().unwrap().as_slice()
+        );
+
+        // the key depends on the mode and patterns
+        assert!(cache.path::<Streaming>(&patterns, &platform).unwrap() != path);
+        assert!(cache.path::<Block>(&patterns!(["foo"]), &platform).unwrap() != path);
+
+        // a corrupted cache file is recompiled and overwritten
+        fs::write(&path, b"corrupted").unwrap();
+
+        let _: BlockDatabase = cache.load(&patterns, &platform).unwrap();
+
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 2 });
+
+        let _: BlockDatabase = cache.load(&patterns, &platform).unwrap();
+
+        assert_eq!(cache.stats(), CacheStats { hits: 2, misses: 2 });
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_database_cache_concurrent_store() {
+        let _ = env_logger::try_init();
+
+        let dir = env::temp_dir().join(format!("hyperscan-cache-concurrent-{}", process::id()));
+        let cache = Arc::new(DatabaseCache::new(&dir));
+
+        let threads = (0..4)
+            .map(|_| {
+                let cache = cache.clone();
+
+                thread::spawn(move || {
+                    for _ in 0..4 {
+                        let _: BlockDatabase = cache.load(&patterns!(["foo", "bar"]), &PlatformInfo::host()).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for t in threads {
+            t.join().unwrap();
+        }
+
+        // every store has renamed its own temporary file
+        let files = fs::read_dir(&dir).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path().extension().unwrap(), "hsdb");
+
+        let stats = cache.stats();
+
+        assert_eq!(stats.hits + stats.misses, 16);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
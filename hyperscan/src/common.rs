@@ -1,6 +1,7 @@
 use std::ffi::CStr;
 use std::fmt;
 use std::marker::PhantomData;
+use std::mem::ManuallyDrop;
 use std::ops::Deref;
 use std::os::raw::c_char;
 use std::ptr;
@@ -9,8 +10,9 @@ use std::slice;
 use libc;
 
 use api::*;
+use constants::*;
 use cptr::CPtr;
-use errors::Error;
+use errors::{Error, Operation};
 use raw::*;
 
 /// A compiled pattern database that can then be used to scan data.
@@ -87,14 +89,14 @@ impl<T: Type> Database for RawDatabase<T> {
         Ok(size)
     }
 
-    fn database_info(&self) -> Result<String, Error> {
+    fn database_info(&self) -> Result<DatabaseInfo, Error> {
         let mut p: *mut c_char = ptr::null_mut();
 
         unsafe {
             check_hs_error!(hs_database_info(self.db, &mut p));
 
             let result = match CStr::from_ptr(p).to_str() {
-                Ok(info) => Ok(info.to_string()),
+                Ok(info) => info.parse(),
                 Err(_) => Err(Error::Invalid),
             };
 
@@ -113,7 +115,10 @@ impl<T: Type> SerializableDatabase<RawDatabase<T>, RawSerializedDatabase> for Ra
         let mut size: usize = 0;
 
         unsafe {
-            check_hs_error!(hs_serialize_database(self.db, &mut bytes, &mut size));
+            check_hs_error!(
+                hs_serialize_database(self.db, &mut bytes, &mut size),
+                Operation::Serialize
+            );
 
             debug!("serialized {} database {:p} to {} bytes", T::name(), self.db, size);
 
@@ -122,14 +127,15 @@ impl<T: Type> SerializableDatabase<RawDatabase<T>, RawSerializedDatabase> for Ra
     }
 
     fn deserialize(bytes: &[u8]) -> Result<RawDatabase<T>, Error> {
+        try!(check_database_mode::<T>(bytes));
+
         let mut db: RawDatabasePtr = ptr::null_mut();
 
         unsafe {
-            check_hs_error!(hs_deserialize_database(
-                bytes.as_ptr() as *const i8,
-                bytes.len(),
-                &mut db,
-            ));
+            check_hs_error!(
+                hs_deserialize_database(bytes.as_ptr() as *const i8, bytes.len(), &mut db),
+                Operation::Deserialize
+            );
 
             debug!(
                 "deserialized {} database to {:p} from {} bytes",
@@ -142,13 +148,31 @@ impl<T: Type> SerializableDatabase<RawDatabase<T>, RawSerializedDatabase> for Ra
         Ok(Self::from_raw(db))
     }
 
-    fn deserialize_at(&self, bytes: &[u8]) -> Result<&RawDatabase<T>, Error> {
-        unsafe {
-            check_hs_error!(hs_deserialize_database_at(
-                bytes.as_ptr() as *const i8,
-                bytes.len(),
+    fn deserialize_at(&mut self, bytes: &[u8]) -> Result<&RawDatabase<T>, Error> {
+        try!(check_database_mode::<T>(bytes));
+
+        let size = try!(bytes
+            .database_size()
+            .map_err(|err| err.with_operation(Operation::Deserialize)));
+        let capacity = try!(self.database_size());
+
+        if capacity < size {
+            debug!(
+                "{} database {:p} of {} bytes is too small to deserialize {} bytes",
+                T::name(),
                 self.db,
-            ));
+                capacity,
+                size
+            );
+
+            return Err(Error::InsufficientSpace.with_operation(Operation::Deserialize));
+        }
+
+        unsafe {
+            check_hs_error!(
+                hs_deserialize_database_at(bytes.as_ptr() as *const i8, bytes.len(), self.db),
+                Operation::Deserialize
+            );
 
             debug!(
                 "deserialized {} database at {:p} from {} bytes",
@@ -162,6 +186,148 @@ impl<T: Type> SerializableDatabase<RawDatabase<T>, RawSerializedDatabase> for Ra
     }
 }
 
+/// The alignment of the memory which a database is deserialized into.
+const DATABASE_ALIGN: usize = 8;
+
+impl<T: Type> RawDatabase<T> {
+    /// Reconstruct a pattern database from a stream of bytes
+    /// previously generated by RawDatabase::serialize() into the caller provided memory.
+    ///
+    /// The memory must be aligned to 8 bytes, and large enough to hold the deserialized database,
+    /// otherwise it fails with `Error::BadAlign` or `Error::InsufficientSpace`.
+    pub fn deserialize_into<'a>(mem: &'a mut [u8], bytes: &[u8]) -> Result<DatabaseRef<'a, T>, Error> {
+        try!(check_database_mode::<T>(bytes));
+
+        let size = try!(bytes
+            .database_size()
+            .map_err(|err| err.with_operation(Operation::Deserialize)));
+
+        if mem.as_ptr() as usize % DATABASE_ALIGN != 0 {
+            return Err(Error::BadAlign.with_operation(Operation::Deserialize));
+        }
+
+        if mem.len() < size {
+            debug!(
+                "memory of {} bytes is too small to deserialize {} database of {} bytes",
+                mem.len(),
+                T::name(),
+                size
+            );
+
+            return Err(Error::InsufficientSpace.with_operation(Operation::Deserialize));
+        }
+
+        let db = mem.as_mut_ptr() as RawDatabasePtr;
+
+        unsafe {
+            check_hs_error!(
+                hs_deserialize_database_at(bytes.as_ptr() as *const i8, bytes.len(), db),
+                Operation::Deserialize
+            );
+        }
+
+        debug!(
+            "deserialized {} database into {:p} from {} bytes",
+            T::name(),
+            db,
+            bytes.len()
+        );
+
+        Ok(DatabaseRef {
+            db: ManuallyDrop::new(RawDatabase::from_raw(db)),
+            _marker: PhantomData,
+        })
+    }
+}
+
+/// A compiled pattern database which was deserialized into borrowed memory.
+///
+/// The memory is owned by the caller, so the database is never freed,
+/// and the memory can't be reused while the database is alive.
+pub struct DatabaseRef<'a, T: Type> {
+    db: ManuallyDrop<RawDatabase<T>>,
+    _marker: PhantomData<&'a mut [u8]>,
+}
+
+impl<'a, T: Type> fmt::Debug for DatabaseRef<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "DatabaseRef<{}>{{db: {:p}}}", T::name(), self.db.db)
+    }
+}
+
+impl<'a, T: Type> Deref for DatabaseRef<'a, T> {
+    type Target = RawDatabase<T>;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.db
+    }
+}
+
+/// An owned memory buffer, correctly aligned to deserialize pattern databases into.
+///
+/// The buffer grows when a larger database is deserialized,
+/// so it could be used to reload databases without allocating each time.
+#[derive(Debug, Default)]
+pub struct DatabaseBuffer {
+    buf: Vec<u64>,
+}
+
+impl DatabaseBuffer {
+    /// Constructs an empty buffer.
+    pub fn new() -> DatabaseBuffer {
+        DatabaseBuffer::default()
+    }
+
+    /// Constructs a buffer which could hold a database of `size` bytes.
+    pub fn with_capacity(size: usize) -> DatabaseBuffer {
+        DatabaseBuffer {
+            buf: vec![0; (size + DATABASE_ALIGN - 1) / DATABASE_ALIGN],
+        }
+    }
+
+    /// The size of the buffer in bytes.
+    pub fn capacity(&self) -> usize {
+        self.buf.len() * DATABASE_ALIGN
+    }
+
+    /// Reconstruct a pattern database from a stream of bytes
+    /// previously generated by RawDatabase::serialize() into the buffer, growing it if needed.
+    pub fn deserialize<T: Type>(&mut self, bytes: &[u8]) -> Result<DatabaseRef<'_, T>, Error> {
+        let size = try!(bytes
+            .database_size()
+            .map_err(|err| err.with_operation(Operation::Deserialize)));
+
+        if self.capacity() < size {
+            *self = DatabaseBuffer::with_capacity(size);
+        }
+
+        let mem = unsafe { slice::from_raw_parts_mut(self.buf.as_mut_ptr() as *mut u8, self.capacity()) };
+
+        RawDatabase::deserialize_into(mem, bytes)
+    }
+}
+
+/// Check the mode of a serialized database matches the expected database type.
+fn check_database_mode<T: Type>(bytes: &[u8]) -> Result<(), Error> {
+    let mode = try!(bytes
+        .database_mode()
+        .map_err(|err| err.with_operation(Operation::Deserialize)));
+
+    if mode != T::mode() {
+        debug!(
+            "serialized database mode {} doesn't match the {} database mode {}",
+            mode,
+            T::name(),
+            T::mode()
+        );
+
+        return Err(Error::DbModeError.with_operation(Operation::Deserialize));
+    }
+
+    Ok(())
+}
+
 unsafe impl<T: Type> Send for RawDatabase<T> {}
 unsafe impl<T: Type> Sync for RawDatabase<T> {}
 
@@ -184,6 +350,114 @@ impl RawDatabase<Streaming> {
     }
 }
 
+/// A compiled pattern database of any mode.
+///
+/// It could be used to load a serialized database of unknown mode,
+/// and then branch on the mode of the database.
+#[derive(Debug)]
+pub enum AnyDatabase {
+    /// Block scan (non-streaming) database.
+    Block(BlockDatabase),
+    /// Streaming database.
+    Streaming(StreamingDatabase),
+    /// Vectored scanning database.
+    Vectored(VectoredDatabase),
+}
+
+impl AnyDatabase {
+    /// Reconstruct a pattern database of any mode from a stream of bytes
+    /// previously generated by RawDatabase::serialize().
+    pub fn deserialize(bytes: &[u8]) -> Result<AnyDatabase, Error> {
+        let mode = try!(bytes
+            .database_mode()
+            .map_err(|err| err.with_operation(Operation::Deserialize)));
+
+        match mode {
+            HS_MODE_BLOCK => BlockDatabase::deserialize(bytes).map(AnyDatabase::Block),
+            HS_MODE_STREAM => StreamingDatabase::deserialize(bytes).map(AnyDatabase::Streaming),
+            HS_MODE_VECTORED => VectoredDatabase::deserialize(bytes).map(AnyDatabase::Vectored),
+            _ => Err(Error::DbModeError.with_operation(Operation::Deserialize)),
+        }
+    }
+
+    /// The block scan (non-streaming) database, if it is.
+    pub fn as_block(&self) -> Option<&BlockDatabase> {
+        match *self {
+            AnyDatabase::Block(ref db) => Some(db),
+            _ => None,
+        }
+    }
+
+    /// The streaming database, if it is.
+    pub fn as_streaming(&self) -> Option<&StreamingDatabase> {
+        match *self {
+            AnyDatabase::Streaming(ref db) => Some(db),
+            _ => None,
+        }
+    }
+
+    /// The vectored scanning database, if it is.
+    pub fn as_vectored(&self) -> Option<&VectoredDatabase> {
+        match *self {
+            AnyDatabase::Vectored(ref db) => Some(db),
+            _ => None,
+        }
+    }
+
+    fn as_database(&self) -> &dyn Database<Target = RawDatabasePtr> {
+        match *self {
+            AnyDatabase::Block(ref db) => db,
+            AnyDatabase::Streaming(ref db) => db,
+            AnyDatabase::Vectored(ref db) => db,
+        }
+    }
+}
+
+impl From<BlockDatabase> for AnyDatabase {
+    fn from(db: BlockDatabase) -> Self {
+        AnyDatabase::Block(db)
+    }
+}
+
+impl From<StreamingDatabase> for AnyDatabase {
+    fn from(db: StreamingDatabase) -> Self {
+        AnyDatabase::Streaming(db)
+    }
+}
+
+impl From<VectoredDatabase> for AnyDatabase {
+    fn from(db: VectoredDatabase) -> Self {
+        AnyDatabase::Vectored(db)
+    }
+}
+
+impl Deref for AnyDatabase {
+    type Target = RawDatabasePtr;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.as_database()
+    }
+}
+
+impl Database for AnyDatabase {
+    fn database_mode(&self) -> u32 {
+        self.as_database().database_mode()
+    }
+
+    fn database_name(&self) -> &'static str {
+        self.as_database().database_name()
+    }
+
+    fn database_size(&self) -> Result<usize, Error> {
+        self.as_database().database_size()
+    }
+
+    fn database_info(&self) -> Result<DatabaseInfo, Error> {
+        self.as_database().database_info()
+    }
+}
+
 pub struct RawSerializedDatabase {
     p: CPtr<u8>,
     len: usize,
@@ -245,25 +519,11 @@ pub mod tests {
 
     const DATABASE_SIZE: usize = 872;
 
-    pub fn validate_database_info(info: &str) -> (Vec<u8>, Option<String>, Option<String>) {
-        if let Some(captures) = Regex::new(r"^Version:\s(\d\.\d\.\d)\sFeatures:\s+(\w+)?\sMode:\s(\w+)$")
-            .unwrap()
-            .captures(info)
-        {
-            let version = captures
-                .get(1)
-                .unwrap()
-                .as_str()
-                .split('.')
-                .flat_map(|s| s.parse())
-                .collect();
-            let features = captures.get(2).map(|m| m.as_str().to_owned());
-            let mode = captures.get(3).map(|m| m.as_str().to_owned());
-
-            (version, features, mode)
-        } else {
-            panic!("fail to parse database info: {}", info);
-        }
+    pub fn validate_database_info(info: &DatabaseInfo) {
+        assert_eq!(info.version, hs_version().unwrap());
+        assert!([HS_MODE_BLOCK, HS_MODE_STREAM, HS_MODE_VECTORED].contains(&info.mode));
+        assert!(info.is_supported_by(&PlatformInfo::host()));
+        assert_eq!(info.to_string().parse::<DatabaseInfo>().unwrap(), *info);
     }
 
     pub fn validate_database_with_size<T: Database>(db: &T, size: usize) {
@@ -307,6 +567,46 @@ pub mod tests {
             .is_match(&format!("{:?}", db)));
     }
 
+    #[test]
+    fn test_database_info() {
+        let _ = env_logger::try_init();
+
+        let info: DatabaseInfo = "Version: 5.0.0 Features:  Mode: STREAM".parse().unwrap();
+
+        assert_eq!(
+            info.version,
+            Version {
+                major: 5,
+                minor: 0,
+                release: 0
+            }
+        );
+        assert_eq!(info.cpu_features, 0);
+        assert_eq!(info.mode, HS_MODE_STREAM);
+
+        let info: DatabaseInfo = "Version: 5.0.0 Features: AVX2 AVX512 Mode: BLOCK".parse().unwrap();
+
+        assert!(info.has_avx2());
+        assert!(info.has_avx512());
+        assert_eq!(info.mode, HS_MODE_BLOCK);
+        assert!(!info.is_supported_by(&PlatformInfo::new(HS_TUNE_FAMILY_GENERIC, 0)));
+        assert!(info.is_supported_by(&PlatformInfo::new(
+            HS_TUNE_FAMILY_SKX,
+            u64::from(HS_CPU_FEATURES_AVX2 | HS_CPU_FEATURES_AVX512)
+        )));
+
+        assert!("Version: 5.0 Mode: BLOCK".parse::<DatabaseInfo>().is_err());
+        assert!("Version: 5.0.0 Features: AVX2".parse::<DatabaseInfo>().is_err());
+
+        let db = BlockDatabase::compile("test", 0, &PlatformInfo::new(HS_TUNE_FAMILY_GENERIC, 0)).unwrap();
+
+        let info = db.database_info().unwrap();
+
+        assert!(!info.has_avx2());
+        assert_eq!(info.mode, HS_MODE_BLOCK);
+        assert_eq!(info.version, hs_version().unwrap());
+    }
+
     #[test]
     fn test_database_serialize() {
         let _ = env_logger::try_init();
@@ -342,10 +642,134 @@ pub mod tests {
     fn test_database_deserialize_at() {
         let _ = env_logger::try_init();
 
-        let db = BlockDatabase::compile("test", 0, &PlatformInfo::null()).unwrap();
+        let mut db = BlockDatabase::compile("test", 0, &PlatformInfo::null()).unwrap();
 
         let data = db.serialize().unwrap();
 
         validate_database(db.deserialize_at(data.as_slice()).unwrap());
     }
+
+    #[test]
+    fn test_database_deserialize_at_insufficient_space() {
+        let _ = env_logger::try_init();
+
+        let mut db = BlockDatabase::compile("test", 0, &PlatformInfo::null()).unwrap();
+
+        let data = BlockDatabase::compile("foo[0-9]+bar|test.*$", 0, &PlatformInfo::null())
+            .unwrap()
+            .serialize()
+            .unwrap();
+
+        assert!(data.database_size().unwrap() > db.database_size().unwrap());
+
+        assert_eq!(
+            db.deserialize_at(data.as_slice()).unwrap_err().kind(),
+            &Error::InsufficientSpace
+        );
+    }
+
+    #[test]
+    fn test_database_deserialize_into() {
+        let _ = env_logger::try_init();
+
+        let db = BlockDatabase::compile("test", 0, &PlatformInfo::null()).unwrap();
+
+        let data = db.serialize().unwrap();
+        let size = data.database_size().unwrap();
+
+        let mut mem = vec![0u64; size / 8 + 1];
+        let mem = unsafe { ::std::slice::from_raw_parts_mut(mem.as_mut_ptr() as *mut u8, mem.len() * 8) };
+
+        assert_eq!(
+            BlockDatabase::deserialize_into(&mut mem[..size - 1], data.as_slice())
+                .unwrap_err()
+                .kind(),
+            &Error::InsufficientSpace
+        );
+        assert_eq!(
+            BlockDatabase::deserialize_into(&mut mem[1..], data.as_slice())
+                .unwrap_err()
+                .kind(),
+            &Error::BadAlign
+        );
+
+        let db = BlockDatabase::deserialize_into(mem, data.as_slice()).unwrap();
+
+        validate_database(&*db);
+    }
+
+    #[test]
+    fn test_database_buffer() {
+        let _ = env_logger::try_init();
+
+        let mut buf = DatabaseBuffer::new();
+
+        assert_eq!(buf.capacity(), 0);
+
+        let data = StreamingDatabase::compile("test", 0, &PlatformInfo::null())
+            .unwrap()
+            .serialize()
+            .unwrap();
+
+        {
+            let db = buf.deserialize::<Streaming>(data.as_slice()).unwrap();
+
+            validate_database(&*db);
+
+            let s = RawScratch::alloc(&*db).unwrap();
+            let st = db.open_stream(0).unwrap();
+
+            st.close(&s, |_| Matching::Continue).unwrap();
+        }
+
+        assert!(buf.capacity() >= data.database_size().unwrap());
+
+        assert!(buf.deserialize::<Block>(data.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_database_deserialize_mode_mismatch() {
+        let _ = env_logger::try_init();
+
+        let db = StreamingDatabase::compile("test", 0, &PlatformInfo::null()).unwrap();
+
+        let data = db.serialize().unwrap();
+
+        assert_eq!(data.database_mode().unwrap(), HS_MODE_STREAM);
+
+        let err = BlockDatabase::deserialize(data.as_slice()).unwrap_err();
+
+        assert_eq!(err.operation(), Some(Operation::Deserialize));
+        assert_eq!(err.kind(), &Error::DbModeError);
+
+        let mut block_db = BlockDatabase::compile("test", 0, &PlatformInfo::null()).unwrap();
+
+        assert_eq!(
+            block_db.deserialize_at(data.as_slice()).unwrap_err().kind(),
+            &Error::DbModeError
+        );
+    }
+
+    #[test]
+    fn test_any_database_deserialize() {
+        let _ = env_logger::try_init();
+
+        let db = StreamingDatabase::compile("test", 0, &PlatformInfo::null()).unwrap();
+
+        let data = db.serialize().unwrap();
+
+        let db = AnyDatabase::deserialize(data.as_slice()).unwrap();
+
+        assert_eq!(db.database_mode(), HS_MODE_STREAM);
+        assert_eq!(db.database_name(), "Streaming");
+        assert!(db.as_block().is_none());
+        assert!(db.as_streaming().is_some());
+
+        validate_database(&db);
+
+        match db {
+            AnyDatabase::Streaming(ref db) => validate_database(db),
+            _ => panic!("unexpected database mode: {:?}", db),
+        }
+    }
 }
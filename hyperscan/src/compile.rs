@@ -5,8 +5,6 @@ use std::os::raw::c_uint;
 use std::ptr;
 use std::str::FromStr;
 
-use regex_syntax;
-
 use api::*;
 use common::RawDatabase;
 use constants::*;
@@ -53,6 +51,12 @@ impl fmt::Display for CompileFlags {
         if self.is_set(HS_FLAG_UCP) {
             try!(write!(f, "W"))
         }
+        if self.is_set(HS_FLAG_PREFILTER) {
+            try!(write!(f, "P"))
+        }
+        if self.is_set(HS_FLAG_SOM_LEFTMOST) {
+            try!(write!(f, "L"))
+        }
         if self.is_set(HS_FLAG_COMBINATION) {
             try!(write!(f, "C"))
         }
@@ -88,6 +92,8 @@ impl CompileFlags {
                 'V' => flags |= HS_FLAG_ALLOWEMPTY,
                 '8' => flags |= HS_FLAG_UTF8,
                 'W' => flags |= HS_FLAG_UCP,
+                'P' => flags |= HS_FLAG_PREFILTER,
+                'L' => flags |= HS_FLAG_SOM_LEFTMOST,
                 'C' => flags |= HS_FLAG_COMBINATION,
                 'Q' => flags |= HS_FLAG_QUIET,
                 _ => return Err(Error::CompilerError(format!("invalid compile flag: {}", c))),
@@ -107,35 +113,226 @@ impl FromStr for CompileFlags {
     }
 }
 
+/// The names of the compile flags, in the order of their characters.
+const FLAG_NAMES: &[(u32, &str)] = &[
+    (HS_FLAG_CASELESS, "CASELESS"),
+    (HS_FLAG_MULTILINE, "MULTILINE"),
+    (HS_FLAG_DOTALL, "DOTALL"),
+    (HS_FLAG_SINGLEMATCH, "SINGLEMATCH"),
+    (HS_FLAG_ALLOWEMPTY, "ALLOWEMPTY"),
+    (HS_FLAG_UTF8, "UTF8"),
+    (HS_FLAG_UCP, "UCP"),
+    (HS_FLAG_PREFILTER, "PREFILTER"),
+    (HS_FLAG_SOM_LEFTMOST, "SOM_LEFTMOST"),
+    (HS_FLAG_COMBINATION, "COMBINATION"),
+    (HS_FLAG_QUIET, "QUIET"),
+];
+
+impl CompileFlags {
+    /// The names of the known flags which are set, such as `CASELESS` for `HS_FLAG_CASELESS`.
+    pub fn names(self) -> Vec<&'static str> {
+        FLAG_NAMES
+            .iter()
+            .filter(|&&(flag, _)| self.is_set(flag))
+            .map(|&(_, name)| name)
+            .collect()
+    }
+
+    /// The flag of the name, with or without the `HS_FLAG_` prefix.
+    pub fn from_name(name: &str) -> Option<u32> {
+        let name = if name.starts_with("HS_FLAG_") {
+            &name["HS_FLAG_".len()..]
+        } else {
+            name
+        };
+
+        FLAG_NAMES
+            .iter()
+            .find(|&&(_, n)| n.eq_ignore_ascii_case(name))
+            .map(|&(flag, _)| flag)
+    }
+
+    /// The bits which are set but have no name.
+    fn unknown(self) -> u32 {
+        FLAG_NAMES.iter().fold(self.0, |bits, &(flag, _)| bits & !flag)
+    }
+}
+
+/// The flags are serialized as a list of their names, such as `["CASELESS", "DOTALL"]`,
+/// the bits without a name are kept as a hexadecimal entry, such as `"0x100000"`.
+///
+/// The names are case insensitive when deserialized, and may have the `HS_FLAG_` prefix.
+#[cfg(feature = "serde")]
+impl ::serde::Serialize for CompileFlags {
+    fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let unknown = self.unknown();
+        let mut names = self.names().into_iter().map(String::from).collect::<Vec<_>>();
+
+        if unknown != 0 {
+            names.push(format!("{:#x}", unknown));
+        }
+
+        serializer.collect_seq(names)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> ::serde::Deserialize<'de> for CompileFlags {
+    fn deserialize<D: ::serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::{self, SeqAccess, Visitor};
+
+        struct FlagsVisitor;
+
+        impl<'de> Visitor<'de> for FlagsVisitor {
+            type Value = CompileFlags;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a list of flag names")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut flags = CompileFlags::default();
+
+                while let Some(name) = try!(seq.next_element::<String>()) {
+                    let flag = if name.starts_with("0x") {
+                        u32::from_str_radix(&name[2..], 16).ok()
+                    } else {
+                        CompileFlags::from_name(&name)
+                    };
+
+                    match flag {
+                        Some(flag) => flags.set(flag),
+                        None => return Err(de::Error::custom(format!("invalid compile flag: {}", name))),
+                    };
+                }
+
+                Ok(flags)
+            }
+        }
+
+        deserializer.deserialize_seq(FlagsVisitor)
+    }
+}
+
 /// Pattern that has matched.
-#[derive(Debug, Clone)]
+///
+/// With the `serde` feature, the unset flags, ID and extended parameters may be omitted.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Pattern {
     /// The NULL-terminated expression to parse.
     pub expression: String,
     /// Flags which modify the behaviour of the expression.
+    #[cfg_attr(feature = "serde", serde(default))]
     pub flags: CompileFlags,
     /// ID number to be associated with the corresponding pattern in the expressions array.
+    #[cfg_attr(feature = "serde", serde(default))]
     pub id: usize,
+    /// The minimum end offset in the data stream at which this expression should match successfully.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub min_offset: Option<u64>,
+    /// The maximum end offset in the data stream at which this expression should match successfully.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub max_offset: Option<u64>,
+    /// The minimum match length (from start to end) required to successfully match this expression.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub min_length: Option<u64>,
+    /// Allow patterns to approximately match within this edit distance.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub edit_distance: Option<u32>,
+    /// Allow patterns to approximately match within this Hamming distance.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub hamming_distance: Option<u32>,
 }
 
 impl Pattern {
+    /// Whether any of the extended parameters is set for the pattern.
+    pub fn has_ext(&self) -> bool {
+        self.min_offset.is_some()
+            || self.max_offset.is_some()
+            || self.min_length.is_some()
+            || self.edit_distance.is_some()
+            || self.hamming_distance.is_some()
+    }
+
+    /// The extended parameters of the pattern, if any of them is set.
+    fn ext(&self) -> Option<hs_expr_ext_t> {
+        if !self.has_ext() {
+            return None;
+        }
+
+        let mut ext = hs_expr_ext_t {
+            flags: 0,
+            min_offset: 0,
+            max_offset: 0,
+            min_length: 0,
+            edit_distance: 0,
+            hamming_distance: 0,
+        };
+
+        if let Some(min_offset) = self.min_offset {
+            ext.flags |= HS_EXT_FLAG_MIN_OFFSET;
+            ext.min_offset = min_offset;
+        }
+        if let Some(max_offset) = self.max_offset {
+            ext.flags |= HS_EXT_FLAG_MAX_OFFSET;
+            ext.max_offset = max_offset;
+        }
+        if let Some(min_length) = self.min_length {
+            ext.flags |= HS_EXT_FLAG_MIN_LENGTH;
+            ext.min_length = min_length;
+        }
+        if let Some(edit_distance) = self.edit_distance {
+            ext.flags |= HS_EXT_FLAG_EDIT_DISTANCE;
+            ext.edit_distance = edit_distance;
+        }
+        if let Some(hamming_distance) = self.hamming_distance {
+            ext.flags |= HS_EXT_FLAG_HAMMING_DISTANCE;
+            ext.hamming_distance = hamming_distance;
+        }
+
+        Some(ext)
+    }
+
+    /// Parse a pattern in the `id:/expression/flags{ext}` format.
+    ///
+    /// The ID and the delimiters are optional, and the expression is taken verbatim
+    /// up to the last `/`, so it doesn't need to be escaped.
+    /// The extended parameters are a comma-separated list of `name=value`,
+    /// where the name is one of `min_offset`, `max_offset`, `min_length`, `edit_distance` and `hamming_distance`.
     pub fn parse(s: &str) -> Result<Pattern, Error> {
         let (id, expr) = match s.find(':') {
-            Some(off) => (s[0..off].parse()?, &s[off + 1..]),
-            None => (0, s),
+            Some(off) if !s.starts_with('/') => (s[0..off].parse()?, &s[off + 1..]),
+            _ => (0, s),
         };
 
         let pattern = match (expr.starts_with('/'), expr.rfind('/')) {
-            (true, Some(end)) if end > 0 => Pattern {
-                expression: expr[1..end].to_owned(),
-                flags: CompileFlags::parse(&expr[end + 1..])?,
-                id,
-            },
+            (true, Some(end)) if end > 0 => {
+                let suffix = &expr[end + 1..];
+                let (flags, ext) = match suffix.find('{') {
+                    Some(off) => (&suffix[..off], Some(&suffix[off..])),
+                    None => (suffix, None),
+                };
+
+                let mut pattern = Pattern {
+                    expression: expr[1..end].to_owned(),
+                    flags: CompileFlags::parse(flags)?,
+                    id,
+                    ..Default::default()
+                };
+
+                if let Some(ext) = ext {
+                    pattern.parse_ext(ext)?;
+                }
+
+                pattern
+            }
 
             _ => Pattern {
                 expression: String::from(expr),
                 flags: CompileFlags::default(),
                 id,
+                ..Default::default()
             },
         };
 
@@ -143,18 +340,55 @@ impl Pattern {
 
         Ok(pattern)
     }
+
+    /// Parse the extended parameters in the `{name=value,...}` format.
+    fn parse_ext(&mut self, s: &str) -> Result<(), Error> {
+        if !s.starts_with('{') || !s.ends_with('}') {
+            return Err(Error::CompilerError(format!("invalid extended parameters: {}", s)));
+        }
+
+        for param in s[1..s.len() - 1].split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let (name, value) = match param.find('=') {
+                Some(off) => (param[..off].trim(), param[off + 1..].trim()),
+                None => return Err(Error::CompilerError(format!("invalid extended parameter: {}", param))),
+            };
+
+            match name {
+                "min_offset" => self.min_offset = Some(value.parse()?),
+                "max_offset" => self.max_offset = Some(value.parse()?),
+                "min_length" => self.min_length = Some(value.parse()?),
+                "edit_distance" => self.edit_distance = Some(value.parse()?),
+                "hamming_distance" => self.hamming_distance = Some(value.parse()?),
+                _ => return Err(Error::CompilerError(format!("invalid extended parameter: {}", name))),
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl fmt::Display for Pattern {
-    #[inline]
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "{}:/{}/{}",
-            self.id,
-            regex_syntax::escape(self.expression.as_str()),
-            self.flags
-        )
+        try!(write!(f, "{}:/{}/{}", self.id, self.expression, self.flags));
+
+        if self.has_ext() {
+            let params = [
+                ("min_offset", self.min_offset),
+                ("max_offset", self.max_offset),
+                ("min_length", self.min_length),
+                ("edit_distance", self.edit_distance.map(u64::from)),
+                ("hamming_distance", self.hamming_distance.map(u64::from)),
+            ];
+
+            let ext = params
+                .iter()
+                .filter_map(|&(name, value)| value.map(|value| format!("{}={}", name, value)))
+                .collect::<Vec<_>>();
+
+            try!(write!(f, "{{{}}}", ext.join(",")));
+        }
+
+        Ok(())
     }
 }
 
@@ -174,15 +408,29 @@ impl Expression for Pattern {
         let mut err: RawCompileErrorPtr = ptr::null_mut();
 
         unsafe {
-            check_compile_error!(
-                hs_expression_info(
-                    expr.as_bytes_with_nul().as_ptr() as *const i8,
-                    self.flags.0,
-                    &mut *info,
-                    &mut err
+            match self.ext() {
+                Some(ref ext) => check_compile_error!(
+                    hs_expression_ext_info(
+                        expr.as_bytes_with_nul().as_ptr() as *const i8,
+                        self.flags.0,
+                        ext,
+                        &mut *info,
+                        &mut err
+                    ),
+                    err,
+                    |_| (Some(self.id), Some(self.expression.clone()))
                 ),
-                err
-            );
+                None => check_compile_error!(
+                    hs_expression_info(
+                        expr.as_bytes_with_nul().as_ptr() as *const i8,
+                        self.flags.0,
+                        &mut *info,
+                        &mut err
+                    ),
+                    err,
+                    |_| (Some(self.id), Some(self.expression.clone()))
+                ),
+            }
 
             let info = ExpressionInfo {
                 min_width: info.as_ref().min_width as usize,
@@ -216,6 +464,7 @@ macro_rules! pattern {
             expression: ::std::convert::From::from($expr),
             flags: ::std::convert::From::from($flags),
             id: $id,
+            ..::std::default::Default::default()
         }
     }};
 }
@@ -258,7 +507,8 @@ impl<T: Type> RawDatabase<T> {
                     &mut db,
                     &mut err
                 ),
-                err
+                err,
+                |_| (None, Some(expression.to_owned()))
             );
         }
 
@@ -281,8 +531,18 @@ impl<T: Type> DatabaseBuilder<RawDatabase<T>> for Pattern {
     /// / This is the function call with which an expression is compiled
     /// into a Hyperscan database which can be passed to the runtime functions
     ///
+    /// The pattern is compiled as a set of one pattern, so its matches are reported with its ID.
+    ///
     fn build_for_platform(&self, platform: &PlatformInfo) -> Result<RawDatabase<T>, Error> {
-        RawDatabase::compile(&self.expression, self.flags.0, platform)
+        vec![self.clone()].build_for_platform(platform)
+    }
+}
+
+/// Resolve the ID and expression of the pattern at the given index.
+fn lookup_pattern(patterns: &[Pattern], index: usize) -> (Option<usize>, Option<String>) {
+    match patterns.get(index) {
+        Some(pattern) => (Some(pattern.id), Some(pattern.expression.clone())),
+        None => (None, None),
     }
 }
 
@@ -295,11 +555,14 @@ impl<T: Type> DatabaseBuilder<RawDatabase<T>> for Patterns {
     /// Each expression can be labelled with a unique integer
     // which is passed into the match callback to identify the pattern that has matched.
     ///
+    /// When any pattern has extended parameters, the set is compiled with `hs_compile_ext_multi`.
+    ///
     fn build_for_platform(&self, platform: &PlatformInfo) -> Result<RawDatabase<T>, Error> {
         let mut expressions = Vec::with_capacity(self.len());
         let mut ptrs = Vec::with_capacity(self.len());
         let mut flags = Vec::with_capacity(self.len());
         let mut ids = Vec::with_capacity(self.len());
+        let mut exts = Vec::with_capacity(self.len());
 
         for pattern in self {
             let expr = try!(CString::new(pattern.expression.as_str()));
@@ -307,6 +570,7 @@ impl<T: Type> DatabaseBuilder<RawDatabase<T>> for Patterns {
             expressions.push(expr);
             flags.push(pattern.flags.0 as c_uint);
             ids.push(pattern.id as c_uint);
+            exts.push(pattern.ext());
         }
 
         for expr in &expressions {
@@ -317,19 +581,43 @@ impl<T: Type> DatabaseBuilder<RawDatabase<T>> for Patterns {
         let mut err: RawCompileErrorPtr = ptr::null_mut();
 
         unsafe {
-            check_compile_error!(
-                hs_compile_multi(
-                    ptrs.as_ptr(),
-                    flags.as_ptr(),
-                    ids.as_ptr(),
-                    self.len() as u32,
-                    T::mode(),
-                    platform.as_ptr(),
-                    &mut db,
-                    &mut err
-                ),
-                err
-            );
+            if exts.iter().any(Option::is_some) {
+                let ext_ptrs = Vec::from_iter(exts.iter().map(|ext| match *ext {
+                    Some(ref ext) => ext as *const hs_expr_ext_t,
+                    None => ptr::null(),
+                }));
+
+                check_compile_error!(
+                    hs_compile_ext_multi(
+                        ptrs.as_ptr(),
+                        flags.as_ptr(),
+                        ids.as_ptr(),
+                        ext_ptrs.as_ptr(),
+                        self.len() as u32,
+                        T::mode(),
+                        platform.as_ptr(),
+                        &mut db,
+                        &mut err
+                    ),
+                    err,
+                    |index| lookup_pattern(self, index)
+                );
+            } else {
+                check_compile_error!(
+                    hs_compile_multi(
+                        ptrs.as_ptr(),
+                        flags.as_ptr(),
+                        ids.as_ptr(),
+                        self.len() as u32,
+                        T::mode(),
+                        platform.as_ptr(),
+                        &mut db,
+                        &mut err
+                    ),
+                    err,
+                    |index| lookup_pattern(self, index)
+                );
+            }
         }
 
         debug!(
@@ -346,6 +634,8 @@ impl<T: Type> DatabaseBuilder<RawDatabase<T>> for Patterns {
 #[cfg(test)]
 pub mod tests {
     extern crate env_logger;
+    #[cfg(feature = "serde")]
+    extern crate serde_json;
 
     use std::ptr;
 
@@ -373,6 +663,10 @@ pub mod tests {
 
         assert_eq!(CompileFlags::parse("ism").unwrap(), flags);
         assert!(CompileFlags::parse("test").is_err());
+
+        assert_eq!(CompileFlags::from_name("HS_FLAG_CASELESS"), Some(HS_FLAG_CASELESS));
+        assert_eq!(CompileFlags::from_name("dotall"), Some(HS_FLAG_DOTALL));
+        assert_eq!(CompileFlags::from_name("HS_FLAG_HS_FLAG_CASELESS"), None);
     }
 
     #[test]
@@ -425,6 +719,97 @@ pub mod tests {
         assert_eq!(p.expression, "t/e/s/t");
         assert_eq!(p.flags, CompileFlags(HS_FLAG_CASELESS));
         assert_eq!(p.id, 0);
+
+        let p = Pattern::parse("/a:b/").unwrap();
+
+        assert_eq!(p.expression, "a:b");
+        assert_eq!(p.id, 0);
+    }
+
+    #[test]
+    fn test_pattern_ext() {
+        let _ = env_logger::try_init();
+
+        let p = Pattern::parse("1:/test/PL{min_offset=10, edit_distance=1}").unwrap();
+
+        assert_eq!(p.expression, "test");
+        assert_eq!(p.flags, CompileFlags(HS_FLAG_PREFILTER | HS_FLAG_SOM_LEFTMOST));
+        assert_eq!(p.min_offset, Some(10));
+        assert_eq!(p.edit_distance, Some(1));
+        assert_eq!(p.max_offset, None);
+
+        assert!(Pattern::parse("1:/test/{min_offset}").is_err());
+        assert!(Pattern::parse("1:/test/{distance=1}").is_err());
+        assert!(Pattern::parse("1:/test/{min_offset=10").is_err());
+    }
+
+    #[test]
+    fn test_pattern_round_trip() {
+        let _ = env_logger::try_init();
+
+        for s in &[
+            "1:/a.b/i",
+            "2:/a\\.b/msHV8W",
+            "3:/t/e/s/t/PL",
+            "4:/foo{2,3}/{min_offset=10,max_offset=20,min_length=5}",
+            "5:/foo/CQ{edit_distance=1,hamming_distance=2}",
+        ] {
+            let p = Pattern::parse(s).unwrap();
+
+            assert_eq!(p.to_string(), *s);
+            assert_eq!(p.to_string().parse::<Pattern>().unwrap(), p);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let _ = env_logger::try_init();
+
+        let mut p = pattern! {"test", flags => HS_FLAG_CASELESS | HS_FLAG_SOM_LEFTMOST, id => 1};
+
+        p.min_offset = Some(10);
+
+        let json = serde_json::to_string(&p).unwrap();
+
+        assert_eq!(
+            json,
+            r#"{"expression":"test","flags":["CASELESS","SOM_LEFTMOST"],"id":1,"min_offset":10}"#
+        );
+        assert_eq!(serde_json::from_str::<Pattern>(&json).unwrap(), p);
+
+        let p: Pattern = serde_json::from_str(r#"{"expression":"test","flags":["HS_FLAG_UTF8", "ucp"]}"#).unwrap();
+
+        assert_eq!(p.flags, CompileFlags(HS_FLAG_UTF8 | HS_FLAG_UCP));
+        assert_eq!(p.id, 0);
+
+        // the bits without a name are kept
+        let flags = CompileFlags(HS_FLAG_CASELESS | 1 << 20);
+        let json = serde_json::to_string(&flags).unwrap();
+
+        assert_eq!(json, r#"["CASELESS","0x100000"]"#);
+        assert_eq!(serde_json::from_str::<CompileFlags>(&json).unwrap(), flags);
+
+        assert!(serde_json::from_str::<CompileFlags>(r#"["FOO"]"#).is_err());
+        assert!(serde_json::from_str::<CompileFlags>(r#"["0xZZ"]"#).is_err());
+        assert!(serde_json::from_str::<CompileFlags>(r#""is""#).is_err());
+        assert!(serde_json::from_str::<CompileFlags>("1").is_err());
+
+        let info = p.info().unwrap();
+
+        assert_eq!(
+            serde_json::from_str::<ExpressionInfo>(&serde_json::to_string(&info).unwrap()).unwrap(),
+            info
+        );
+
+        let m = Match {
+            id: 1,
+            from: 0,
+            to: 4,
+            flags: 0,
+        };
+
+        assert_eq!(serde_json::from_str::<Match>(r#"{"id":1,"from":0,"to":4}"#).unwrap(), m);
     }
 
     #[test]
@@ -465,6 +850,101 @@ pub mod tests {
         validate_database(&db);
     }
 
+    #[test]
+    fn test_pattern_build_with_ext() {
+        let _ = env_logger::try_init();
+
+        let p = Pattern {
+            min_offset: Some(10),
+            max_offset: Some(20),
+            ..pattern! {"test"}
+        };
+
+        assert!(p.has_ext());
+        assert!(!pattern! {"test"}.has_ext());
+
+        let info = p.info().unwrap();
+
+        assert_eq!(info.min_width, 4);
+        assert_eq!(info.max_width, 4);
+
+        let db: BlockDatabase = p.build().unwrap();
+
+        validate_database(&db);
+
+        let p = Pattern {
+            edit_distance: Some(1),
+            ..pattern! {"test"}
+        };
+
+        assert_eq!(p.info().unwrap().min_width, 3);
+    }
+
+    #[test]
+    fn test_pattern_build_with_id() {
+        let _ = env_logger::try_init();
+
+        let p = pattern! {"test", flags => 0, id => 7};
+        let ext = Pattern {
+            min_offset: Some(4),
+            ..p.clone()
+        };
+
+        // the id is reported with or without the extended parameters
+        for p in &[p, ext] {
+            let db: BlockDatabase = p.build().unwrap();
+            let s = db.alloc().unwrap();
+            let mut matches = vec![];
+
+            db.scan("the test", 0, &s, |m: Match| {
+                matches.push((m.id, m.to));
+
+                Matching::Continue
+            })
+            .unwrap();
+
+            assert_eq!(matches, vec![(7, 8)]);
+        }
+    }
+
+    #[test]
+    fn test_patterns_build_with_ext() {
+        let _ = env_logger::try_init();
+
+        let mut patterns = patterns!(["test", "foo", "bar"]);
+
+        patterns[0].min_length = Some(4);
+        patterns[2].hamming_distance = Some(1);
+
+        let db: BlockDatabase = patterns.build().unwrap();
+
+        validate_database(&db);
+    }
+
+    #[test]
+    fn test_patterns_build_error() {
+        let _ = env_logger::try_init();
+
+        let patterns = patterns!(["test", "a(b", "bar"]);
+
+        match DatabaseBuilder::<BlockDatabase>::build(&patterns).err() {
+            Some(Error::ExpressionError(err)) => {
+                assert_eq!(err.index, 1);
+                assert_eq!(err.id, Some(2));
+                assert_eq!(err.expression, Some("a(b".to_owned()));
+            }
+            err => panic!("unexpected result: {:?}", err),
+        }
+
+        match pattern! {"a(b", flags => 0, id => 3}.info().err() {
+            Some(Error::ExpressionError(err)) => {
+                assert_eq!(err.index, 0);
+                assert_eq!(err.id, Some(3));
+            }
+            err => panic!("unexpected result: {:?}", err),
+        }
+    }
+
     #[test]
     fn test_patterns_build() {
         let _ = env_logger::try_init();
@@ -298,6 +298,31 @@ pub const HS_FLAG_COMBINATION: u32 = 512;
  */
 pub const HS_FLAG_QUIET: u32 = 1024;
 
+/**
+ * Flag indicating that the hs_expr_ext::min_offset field is used.
+ */
+pub const HS_EXT_FLAG_MIN_OFFSET: u64 = 1;
+
+/**
+ * Flag indicating that the hs_expr_ext::max_offset field is used.
+ */
+pub const HS_EXT_FLAG_MAX_OFFSET: u64 = 2;
+
+/**
+ * Flag indicating that the hs_expr_ext::min_length field is used.
+ */
+pub const HS_EXT_FLAG_MIN_LENGTH: u64 = 4;
+
+/**
+ * Flag indicating that the hs_expr_ext::edit_distance field is used.
+ */
+pub const HS_EXT_FLAG_EDIT_DISTANCE: u64 = 8;
+
+/**
+ * Flag indicating that the hs_expr_ext::hamming_distance field is used.
+ */
+pub const HS_EXT_FLAG_HAMMING_DISTANCE: u64 = 16;
+
 /**
  * CPU features flag - Intel(R) Advanced Vector Extensions 2 (Intel(R) AVX2)
  *
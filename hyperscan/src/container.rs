@@ -0,0 +1,535 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::mem;
+
+use api::*;
+use common::{AnyDatabase, RawDatabase};
+use compile::{CompileFlags, Pattern, Patterns};
+use constants::*;
+use errors::Error;
+
+/// The magic header of a database container.
+const MAGIC: &[u8; 4] = b"HSDB";
+
+/// The version of the container format.
+const FORMAT_VERSION: u16 = 1;
+
+/// A self-describing container of a compiled pattern database.
+///
+/// Besides the serialized Hyperscan database, the container keeps the mode, CPU features and tuning
+/// of the platform which the database was compiled for, the patterns it was compiled from and some user metadata,
+/// so a loaded database could map the ids of matches back to the patterns.
+///
+/// The container is encoded as a magic header `HSDB` and the format version,
+/// followed by the little endian encoded fields.
+#[derive(Clone)]
+pub struct DatabaseContainer {
+    info: DatabaseInfo,
+    tune: u32,
+    patterns: Patterns,
+    index: HashMap<usize, usize>,
+    metadata: BTreeMap<String, String>,
+    database: Vec<u8>,
+}
+
+impl fmt::Debug for DatabaseContainer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "DatabaseContainer{{info: {}, tune: {}, patterns: {}, metadata: {:?}, database: {} bytes}}",
+            self.info,
+            self.tune,
+            self.patterns.len(),
+            self.metadata,
+            self.database.len()
+        )
+    }
+}
+
+impl DatabaseContainer {
+    /// Constructs a container with the database and the patterns which it was compiled from.
+    ///
+    /// The database is expected to be compiled for the host, whose tuning is recorded.
+    pub fn new<T: Type>(db: &RawDatabase<T>, patterns: Patterns) -> Result<DatabaseContainer, Error> {
+        Self::with_tune(db, patterns, PlatformInfo::host().tune())
+    }
+
+    /// Compile the patterns in the mode of `T` for the platform, and constructs a container with the database.
+    pub fn build_for_platform<T: Type>(
+        patterns: Patterns,
+        platform: &PlatformInfo,
+    ) -> Result<DatabaseContainer, Error> {
+        let db: RawDatabase<T> = try!(patterns.build_for_platform(platform));
+
+        Self::with_tune(&db, patterns, platform.tune())
+    }
+
+    fn with_tune<T: Type>(db: &RawDatabase<T>, patterns: Patterns, tune: u32) -> Result<DatabaseContainer, Error> {
+        let database = try!(db.serialize()).as_slice().to_vec();
+        let info = try!(database.as_slice().database_info());
+
+        Ok(DatabaseContainer {
+            info,
+            tune,
+            index: index_patterns(&patterns),
+            patterns,
+            metadata: BTreeMap::new(),
+            database,
+        })
+    }
+
+    /// Add a user metadata to the container.
+    pub fn with_metadata<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// The version, platform and mode which the database was compiled for.
+    pub fn info(&self) -> &DatabaseInfo {
+        &self.info
+    }
+
+    /// The mode which the database was compiled for.
+    pub fn mode(&self) -> u32 {
+        self.info.mode
+    }
+
+    /// The tuning of the platform which the database was compiled for.
+    ///
+    /// The tuning is only an optimization hint, a database runs on any platform with the required CPU features.
+    pub fn tune(&self) -> u32 {
+        self.tune
+    }
+
+    /// Whether the database could run on the platform, which must have the CPU features it was compiled for.
+    pub fn is_supported_by(&self, platform: &PlatformInfo) -> bool {
+        self.info.is_supported_by(platform)
+    }
+
+    /// The patterns which the database was compiled from.
+    pub fn patterns(&self) -> &Patterns {
+        &self.patterns
+    }
+
+    /// Find the pattern of a match id.
+    pub fn pattern(&self, id: u32) -> Option<&Pattern> {
+        self.index.get(&(id as usize)).map(|&index| &self.patterns[index])
+    }
+
+    /// The user metadata.
+    pub fn metadata(&self) -> &BTreeMap<String, String> {
+        &self.metadata
+    }
+
+    /// The serialized Hyperscan database.
+    pub fn serialized_database(&self) -> &[u8] {
+        &self.database
+    }
+
+    /// Reconstruct the pattern database, which must be compiled for the mode of `T` and the host.
+    pub fn database<T: Type>(&self) -> Result<RawDatabase<T>, Error> {
+        try!(self.check_host());
+
+        RawDatabase::deserialize(&self.database)
+    }
+
+    /// Reconstruct the pattern database of any mode, which must be compiled for the host.
+    pub fn any_database(&self) -> Result<AnyDatabase, Error> {
+        try!(self.check_host());
+
+        AnyDatabase::deserialize(&self.database)
+    }
+
+    fn check_host(&self) -> Result<(), Error> {
+        if self.is_supported_by(&PlatformInfo::host()) {
+            Ok(())
+        } else {
+            Err(Error::DbPlatformError)
+        }
+    }
+
+    /// Encode the container to bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Encoder(Vec::with_capacity(self.database.len() + 256));
+
+        w.put_bytes(MAGIC);
+        w.put_u16(FORMAT_VERSION);
+
+        w.put_u32(self.info.mode);
+        w.put_u8(self.info.version.major);
+        w.put_u8(self.info.version.minor);
+        w.put_u8(self.info.version.release);
+        w.put_u64(self.info.cpu_features);
+        w.put_u32(self.tune);
+
+        w.put_u32(self.patterns.len() as u32);
+
+        for pattern in &self.patterns {
+            w.put_u64(pattern.id as u64);
+            w.put_u32(pattern.flags.0);
+            w.put_str(&pattern.expression);
+
+            let mut ext_flags = 0;
+
+            if pattern.min_offset.is_some() {
+                ext_flags |= HS_EXT_FLAG_MIN_OFFSET;
+            }
+            if pattern.max_offset.is_some() {
+                ext_flags |= HS_EXT_FLAG_MAX_OFFSET;
+            }
+            if pattern.min_length.is_some() {
+                ext_flags |= HS_EXT_FLAG_MIN_LENGTH;
+            }
+            if pattern.edit_distance.is_some() {
+                ext_flags |= HS_EXT_FLAG_EDIT_DISTANCE;
+            }
+            if pattern.hamming_distance.is_some() {
+                ext_flags |= HS_EXT_FLAG_HAMMING_DISTANCE;
+            }
+
+            w.put_u64(ext_flags);
+            w.put_u64(pattern.min_offset.unwrap_or_default());
+            w.put_u64(pattern.max_offset.unwrap_or_default());
+            w.put_u64(pattern.min_length.unwrap_or_default());
+            w.put_u32(pattern.edit_distance.unwrap_or_default());
+            w.put_u32(pattern.hamming_distance.unwrap_or_default());
+        }
+
+        w.put_u32(self.metadata.len() as u32);
+
+        for (key, value) in &self.metadata {
+            w.put_str(key);
+            w.put_str(value);
+        }
+
+        w.put_u64(self.database.len() as u64);
+        w.put_bytes(&self.database);
+
+        w.0
+    }
+
+    /// Decode the container from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<DatabaseContainer, Error> {
+        let mut r = Decoder(bytes);
+
+        if try!(r.get_bytes(MAGIC.len())) != MAGIC {
+            return Err(Error::ContainerError("invalid magic header".to_owned()));
+        }
+
+        let format_version = try!(r.get_u16());
+
+        if format_version != FORMAT_VERSION {
+            return Err(Error::ContainerError(format!(
+                "unsupported format version: {}",
+                format_version
+            )));
+        }
+
+        let mode = try!(r.get_u32());
+        let version = Version {
+            major: try!(r.get_u8()),
+            minor: try!(r.get_u8()),
+            release: try!(r.get_u8()),
+        };
+        let cpu_features = try!(r.get_u64());
+        let tune = try!(r.get_u32());
+
+        let count = try!(r.get_u32());
+        let mut patterns = Vec::new();
+
+        for _ in 0..count {
+            let id = try!(r.get_u64()) as usize;
+            let flags = CompileFlags(try!(r.get_u32()));
+            let expression = try!(r.get_str());
+            let ext_flags = try!(r.get_u64());
+            let min_offset = try!(r.get_u64());
+            let max_offset = try!(r.get_u64());
+            let min_length = try!(r.get_u64());
+            let edit_distance = try!(r.get_u32());
+            let hamming_distance = try!(r.get_u32());
+
+            patterns.push(Pattern {
+                expression,
+                flags,
+                id,
+                min_offset: ext_param(ext_flags, HS_EXT_FLAG_MIN_OFFSET, min_offset),
+                max_offset: ext_param(ext_flags, HS_EXT_FLAG_MAX_OFFSET, max_offset),
+                min_length: ext_param(ext_flags, HS_EXT_FLAG_MIN_LENGTH, min_length),
+                edit_distance: ext_param(ext_flags, HS_EXT_FLAG_EDIT_DISTANCE, edit_distance),
+                hamming_distance: ext_param(ext_flags, HS_EXT_FLAG_HAMMING_DISTANCE, hamming_distance),
+            });
+        }
+
+        let count = try!(r.get_u32());
+        let mut metadata = BTreeMap::new();
+
+        for _ in 0..count {
+            let key = try!(r.get_str());
+            let value = try!(r.get_str());
+
+            metadata.insert(key, value);
+        }
+
+        let len = try!(r.get_u64());
+        let database = try!(r.get_bytes(len as usize)).to_vec();
+
+        if !r.0.is_empty() {
+            return Err(Error::ContainerError(format!("{} trailing bytes", r.0.len())));
+        }
+
+        debug!(
+            "decoded container of {} database with {} patterns from {} bytes",
+            version,
+            patterns.len(),
+            bytes.len()
+        );
+
+        Ok(DatabaseContainer {
+            info: DatabaseInfo {
+                version,
+                cpu_features,
+                mode,
+            },
+            tune,
+            index: index_patterns(&patterns),
+            patterns,
+            metadata,
+            database,
+        })
+    }
+
+    /// Write the encoded container to a writer.
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(&self.to_bytes())
+    }
+
+    /// Read an encoded container from a reader.
+    pub fn read_from<R: Read>(r: &mut R) -> io::Result<DatabaseContainer> {
+        let mut bytes = Vec::new();
+
+        try!(r.read_to_end(&mut bytes));
+
+        DatabaseContainer::from_bytes(&bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+/// Index the patterns by id, the first pattern wins when an id is shared.
+fn index_patterns(patterns: &[Pattern]) -> HashMap<usize, usize> {
+    let mut index = HashMap::with_capacity(patterns.len());
+
+    for (i, pattern) in patterns.iter().enumerate() {
+        index.entry(pattern.id).or_insert(i);
+    }
+
+    index
+}
+
+fn ext_param<T>(ext_flags: u64, flag: u64, value: T) -> Option<T> {
+    if ext_flags & flag == flag {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+struct Encoder(Vec<u8>);
+
+impl Encoder {
+    fn put_bytes(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes)
+    }
+
+    fn put_u8(&mut self, n: u8) {
+        self.0.push(n)
+    }
+
+    fn put_u16(&mut self, n: u16) {
+        self.put_bytes(&n.to_le_bytes())
+    }
+
+    fn put_u32(&mut self, n: u32) {
+        self.put_bytes(&n.to_le_bytes())
+    }
+
+    fn put_u64(&mut self, n: u64) {
+        self.put_bytes(&n.to_le_bytes())
+    }
+
+    fn put_str(&mut self, s: &str) {
+        self.put_u32(s.len() as u32);
+        self.put_bytes(s.as_bytes())
+    }
+}
+
+struct Decoder<'a>(&'a [u8]);
+
+impl<'a> Decoder<'a> {
+    fn get_bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.0.len() < len {
+            return Err(Error::ContainerError("unexpected end of data".to_owned()));
+        }
+
+        let (bytes, rest) = self.0.split_at(len);
+
+        self.0 = rest;
+
+        Ok(bytes)
+    }
+
+    fn get_u8(&mut self) -> Result<u8, Error> {
+        self.get_bytes(1).map(|bytes| bytes[0])
+    }
+
+    fn get_u16(&mut self) -> Result<u16, Error> {
+        let mut buf = [0; 2];
+
+        buf.copy_from_slice(try!(self.get_bytes(mem::size_of::<u16>())));
+
+        Ok(u16::from_le_bytes(buf))
+    }
+
+    fn get_u32(&mut self) -> Result<u32, Error> {
+        let mut buf = [0; 4];
+
+        buf.copy_from_slice(try!(self.get_bytes(mem::size_of::<u32>())));
+
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn get_u64(&mut self) -> Result<u64, Error> {
+        let mut buf = [0; 8];
+
+        buf.copy_from_slice(try!(self.get_bytes(mem::size_of::<u64>())));
+
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    fn get_str(&mut self) -> Result<String, Error> {
+        let len = try!(self.get_u32()) as usize;
+        let bytes = try!(self.get_bytes(len));
+
+        String::from_utf8(bytes.to_vec()).map_err(|_| Error::ContainerError("invalid UTF-8 string".to_owned()))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    extern crate env_logger;
+
+    use super::super::*;
+
+    #[test]
+    fn test_database_container() {
+        let _ = env_logger::try_init();
+
+        let mut patterns = patterns!(["foo", "bar"], flags => HS_FLAG_CASELESS);
+
+        patterns[1].min_offset = Some(4);
+        patterns[1].edit_distance = Some(1);
+
+        let db: BlockDatabase = patterns.build().unwrap();
+
+        let container = DatabaseContainer::new(&db, patterns)
+            .unwrap()
+            .with_metadata("name", "test rules");
+
+        let bytes = container.to_bytes();
+
+        assert_eq!(&bytes[..4], b"HSDB");
+
+        let container = DatabaseContainer::from_bytes(&bytes).unwrap();
+
+        assert_eq!(container.mode(), HS_MODE_BLOCK);
+        assert_eq!(container.tune(), PlatformInfo::host().tune());
+        assert!(container.is_supported_by(&PlatformInfo::host()));
+        assert_eq!(container.info().version, hs_version().unwrap());
+        assert_eq!(container.metadata()["name"], "test rules");
+        assert_eq!(container.patterns().len(), 2);
+
+        let pattern = container.pattern(2).unwrap();
+
+        assert_eq!(pattern.expression, "bar");
+        assert_eq!(pattern.flags, CompileFlags(HS_FLAG_CASELESS));
+        assert_eq!(pattern.min_offset, Some(4));
+        assert_eq!(pattern.max_offset, None);
+        assert_eq!(pattern.edit_distance, Some(1));
+
+        assert!(container.database::<Streaming>().is_err());
+
+        let db: BlockDatabase = container.database().unwrap();
+        let s = db.alloc().unwrap();
+        let mut matches = vec![];
+
+        db.scan("FOO and bar", 0, &s, |m: Match| {
+            matches.push(container.pattern(m.id).unwrap().expression.clone());
+
+            Matching::Continue
+        })
+        .unwrap();
+
+        assert_eq!(matches, vec!["foo", "bar"]);
+    }
+
+    #[test]
+    fn test_database_container_malformed() {
+        let _ = env_logger::try_init();
+
+        let patterns = patterns!(["test"]);
+        let db: StreamingDatabase = patterns.build().unwrap();
+        let bytes = DatabaseContainer::new(&db, patterns).unwrap().to_bytes();
+
+        match DatabaseContainer::from_bytes(&bytes[..bytes.len() - 1]) {
+            Err(Error::ContainerError(_)) => {}
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        let mut corrupted = bytes.clone();
+
+        corrupted[0] = b'X';
+
+        assert!(DatabaseContainer::from_bytes(&corrupted).is_err());
+
+        let container = DatabaseContainer::read_from(&mut bytes.as_slice()).unwrap();
+
+        match container.any_database().unwrap() {
+            AnyDatabase::Streaming(_) => {}
+            db => panic!("unexpected database: {:?}", db),
+        }
+    }
+
+    #[test]
+    fn test_database_container_platform() {
+        let _ = env_logger::try_init();
+
+        let host = PlatformInfo::host();
+        let tune = if host.tune() == HS_TUNE_FAMILY_SNB {
+            HS_TUNE_FAMILY_HSW
+        } else {
+            HS_TUNE_FAMILY_SNB
+        };
+        let platform = PlatformInfo::new(tune, host.cpu_features());
+
+        let container = DatabaseContainer::build_for_platform::<Block>(patterns!(["foo", "bar"]), &platform).unwrap();
+        let container = DatabaseContainer::from_bytes(&container.to_bytes()).unwrap();
+
+        assert_eq!(container.tune(), tune);
+
+        // the tuning is only a hint, the database still runs on the host
+        assert!(container.is_supported_by(&platform));
+        assert!(container.is_supported_by(&host));
+
+        let db: BlockDatabase = container.database().unwrap();
+        let s = db.alloc().unwrap();
+        let mut matches = vec![];
+
+        db.scan("foo bar", 0, &s, |m: Match| {
+            matches.push(m.id);
+
+            Matching::Continue
+        })
+        .unwrap();
+
+        assert_eq!(matches, vec![1, 2]);
+    }
+}
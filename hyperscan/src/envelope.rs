@@ -0,0 +1,231 @@
+use std::fmt;
+
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+use api::*;
+use common::RawDatabase;
+use errors::{Error, IntegrityError};
+
+/// The magic header of a sealed envelope.
+const MAGIC: &[u8; 4] = b"HSEV";
+
+/// The version of the envelope format.
+const FORMAT_VERSION: u16 = 1;
+
+/// The payload is only protected by the checksum.
+const ALGORITHM_NONE: u8 = 0;
+
+/// The payload is signed with HMAC-SHA256.
+const ALGORITHM_HMAC_SHA256: u8 = 1;
+
+/// The size of the header, including magic, version, algorithm and payload length.
+const HEADER_LEN: usize = 4 + 2 + 1 + 8;
+
+/// The size of SHA256 checksum and HMAC-SHA256 signature.
+const DIGEST_LEN: usize = 32;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// An integrity envelope around serialized databases or containers.
+///
+/// The sealed envelope contains a header, the payload, a SHA256 checksum of the header and payload,
+/// and an optional HMAC-SHA256 signature of all of them when it is sealed with a key.
+///
+/// The envelope is verified before the payload is returned, so tampered or truncated bytes
+/// never reach `hs_deserialize_database`. An envelope with a key requires a valid signature,
+/// while an envelope without a key only verifies the checksum of a signed payload.
+#[derive(Clone, Default)]
+pub struct Envelope {
+    key: Option<Vec<u8>>,
+}
+
+impl fmt::Debug for Envelope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Envelope{{signed: {}}}", self.key.is_some())
+    }
+}
+
+impl Envelope {
+    /// Constructs an envelope which only protects the payload with a checksum.
+    pub fn new() -> Envelope {
+        Envelope::default()
+    }
+
+    /// Constructs an envelope which signs the payload with the key, and requires a valid signature.
+    pub fn with_key<K: AsRef<[u8]>>(key: K) -> Envelope {
+        Envelope {
+            key: Some(key.as_ref().to_vec()),
+        }
+    }
+
+    fn mac(&self) -> Option<HmacSha256> {
+        self.key
+            .as_ref()
+            .map(|key| HmacSha256::new_varkey(key).expect("HMAC accepts keys of any size"))
+    }
+
+    /// Seal the payload in the envelope.
+    pub fn seal(&self, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len() + DIGEST_LEN * 2);
+
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        bytes.push(if self.key.is_some() {
+            ALGORITHM_HMAC_SHA256
+        } else {
+            ALGORITHM_NONE
+        });
+        bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(payload);
+
+        let checksum = Sha256::digest(&bytes);
+
+        bytes.extend_from_slice(&checksum);
+
+        if let Some(mut mac) = self.mac() {
+            mac.input(&bytes);
+
+            bytes.extend_from_slice(&mac.result().code());
+        }
+
+        bytes
+    }
+
+    /// Verify the sealed envelope, and return its payload.
+    pub fn open<'a>(&self, bytes: &'a [u8]) -> Result<&'a [u8], Error> {
+        self.verify(bytes).map_err(Error::IntegrityError)
+    }
+
+    fn verify<'a>(&self, bytes: &'a [u8]) -> Result<&'a [u8], IntegrityError> {
+        if bytes.len() < HEADER_LEN || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(IntegrityError::Malformed);
+        }
+
+        let mut buf = [0; 2];
+
+        buf.copy_from_slice(&bytes[4..6]);
+
+        if u16::from_le_bytes(buf) != FORMAT_VERSION {
+            return Err(IntegrityError::Unsupported);
+        }
+
+        let signature_len = match bytes[6] {
+            ALGORITHM_NONE => 0,
+            ALGORITHM_HMAC_SHA256 => DIGEST_LEN,
+            _ => return Err(IntegrityError::Unsupported),
+        };
+
+        let mut buf = [0; 8];
+
+        buf.copy_from_slice(&bytes[7..HEADER_LEN]);
+
+        let payload_len = u64::from_le_bytes(buf);
+
+        match payload_len.checked_add((HEADER_LEN + DIGEST_LEN + signature_len) as u64) {
+            Some(len) if len == bytes.len() as u64 => {}
+            _ => return Err(IntegrityError::Malformed),
+        }
+
+        let (sealed, signature) = bytes.split_at(bytes.len() - signature_len);
+        let (content, checksum) = sealed.split_at(sealed.len() - DIGEST_LEN);
+
+        if Sha256::digest(content).as_slice() != checksum {
+            return Err(IntegrityError::ChecksumMismatch);
+        }
+
+        if let Some(mut mac) = self.mac() {
+            if signature.is_empty() {
+                return Err(IntegrityError::MissingSignature);
+            }
+
+            mac.input(sealed);
+
+            if mac.verify(signature).is_err() {
+                return Err(IntegrityError::SignatureMismatch);
+            }
+        }
+
+        Ok(&content[HEADER_LEN..])
+    }
+
+    /// Serialize the pattern database, and seal it in the envelope.
+    pub fn seal_database<T: Type>(&self, db: &RawDatabase<T>) -> Result<Vec<u8>, Error> {
+        db.serialize().map(|data| self.seal(data.as_slice()))
+    }
+
+    /// Verify the sealed envelope, and reconstruct the pattern database from its payload.
+    pub fn open_database<T: Type>(&self, bytes: &[u8]) -> Result<RawDatabase<T>, Error> {
+        RawDatabase::deserialize(try!(self.open(bytes)))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    extern crate env_logger;
+
+    use super::super::common::tests::*;
+    use super::super::*;
+
+    #[test]
+    fn test_envelope() {
+        let _ = env_logger::try_init();
+
+        let db: BlockDatabase = pattern! {"test"}.build().unwrap();
+
+        let envelope = Envelope::new();
+        let bytes = envelope.seal_database(&db).unwrap();
+
+        let db: BlockDatabase = envelope.open_database(&bytes).unwrap();
+
+        validate_database(&db);
+
+        let mut tampered = bytes.clone();
+        let len = tampered.len();
+
+        tampered[len / 2] ^= 0xFF;
+
+        assert_eq!(
+            envelope.open(&tampered).unwrap_err(),
+            Error::IntegrityError(IntegrityError::ChecksumMismatch)
+        );
+        assert_eq!(
+            envelope.open(&bytes[..len - 1]).unwrap_err(),
+            Error::IntegrityError(IntegrityError::Malformed)
+        );
+        assert_eq!(
+            envelope.open(b"HSEV").unwrap_err(),
+            Error::IntegrityError(IntegrityError::Malformed)
+        );
+        assert_eq!(
+            Envelope::with_key("secret").open(&bytes).unwrap_err(),
+            Error::IntegrityError(IntegrityError::MissingSignature)
+        );
+    }
+
+    #[test]
+    fn test_envelope_signed() {
+        let _ = env_logger::try_init();
+
+        let envelope = Envelope::with_key("secret");
+        let bytes = envelope.seal(b"payload");
+
+        assert_eq!(envelope.open(&bytes).unwrap(), b"payload");
+        assert_eq!(Envelope::new().open(&bytes).unwrap(), b"payload");
+
+        assert_eq!(
+            Envelope::with_key("other").open(&bytes).unwrap_err(),
+            Error::IntegrityError(IntegrityError::SignatureMismatch)
+        );
+
+        let mut tampered = bytes.clone();
+        let len = tampered.len();
+
+        tampered[len - 1] ^= 0xFF;
+
+        assert_eq!(
+            envelope.open(&tampered).unwrap_err(),
+            Error::IntegrityError(IntegrityError::SignatureMismatch)
+        );
+    }
+}
@@ -8,6 +8,7 @@ use raw::*;
 
 /// Error Codes
 #[derive(Debug, PartialEq, Clone)]
+#[non_exhaustive]
 pub enum Error {
     /// A parameter passed to this function was invalid.
     Invalid,
@@ -19,7 +20,11 @@ pub enum Error {
     /// but that the callback function requested that scanning cease after a match was located.
     ScanTerminated,
     /// The pattern compiler failed with more detail.
+    ///
+    /// The error is not specific to an expression, such as a database that is too large.
     CompilerError(String),
+    /// The pattern compiler failed on a specific expression.
+    ExpressionError(ExpressionError),
     /// The given database was built for a different version of Hyperscan.
     DbVersionError,
     /// The given database was built for a different platform (i.e., CPU type).
@@ -34,8 +39,30 @@ pub enum Error {
     /// did not correctly return memory suitably aligned
     /// for the largest representable data type on this platform.
     BadAlloc,
+    /// The scratch region was already in use.
+    ///
+    /// A separate scratch region is required for every concurrent caller of the Hyperscan API.
+    ScratchInUse,
+    /// Unsupported CPU architecture.
+    ///
+    /// At a minimum, Hyperscan requires Supplemental Streaming SIMD Extensions 3 (SSSE3).
+    ArchError,
+    /// Provided buffer was too small.
+    InsufficientSpace,
+    /// The data is too large to be passed to Hyperscan in one call.
+    DataTooLarge(usize),
+    /// There are too many data segments to be passed to Hyperscan in one call.
+    TooManySegments(usize),
+    /// The database container is malformed.
+    ContainerError(String),
+    /// The integrity check of a sealed envelope failed.
+    IntegrityError(IntegrityError),
     /// Unknown error code
     Failed(i32),
+    /// A Hyperscan operation failed with the error.
+    Operation(Operation, Box<Error>),
+    /// A pattern file failed to parse at the line.
+    LineError(usize, Box<Error>),
     /// An error which can be returned when parsing an integer.
     ParseError(::std::num::ParseIntError),
     /// An error returned from CString::new to indicate
@@ -43,6 +70,144 @@ pub enum Error {
     NulError(::std::ffi::NulError),
 }
 
+/// Details of a compile error caused by a specific expression.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ExpressionError {
+    /// A human-readable error message describing the error.
+    pub message: String,
+    /// The zero-based number of the expression that caused the error.
+    pub index: usize,
+    /// ID number of the pattern that caused the error, if known.
+    pub id: Option<usize>,
+    /// The expression that caused the error, if known.
+    pub expression: Option<String>,
+}
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{} at expression #{}", self.message, self.index));
+
+        if let Some(id) = self.id {
+            try!(write!(f, " (id {})", id));
+        }
+        if let Some(ref expression) = self.expression {
+            try!(write!(f, " `{}`", expression));
+        }
+
+        Ok(())
+    }
+}
+
+/// The reason why the integrity check of a sealed envelope failed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum IntegrityError {
+    /// The envelope is truncated or has an invalid header.
+    Malformed,
+    /// The envelope was sealed with an unsupported version or algorithm.
+    Unsupported,
+    /// The checksum doesn't match the payload.
+    ChecksumMismatch,
+    /// The envelope is not signed, but a signature is required.
+    MissingSignature,
+    /// The signature doesn't match the payload.
+    SignatureMismatch,
+}
+
+impl fmt::Display for IntegrityError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match *self {
+                IntegrityError::Malformed => "malformed envelope",
+                IntegrityError::Unsupported => "unsupported envelope",
+                IntegrityError::ChecksumMismatch => "checksum mismatch",
+                IntegrityError::MissingSignature => "missing signature",
+                IntegrityError::SignatureMismatch => "signature mismatch",
+            }
+        )
+    }
+}
+
+/// The Hyperscan operation which may fail.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Operation {
+    /// Compile expressions to a database.
+    Compile,
+    /// Allocate a scratch space.
+    AllocScratch,
+    /// Clone a scratch space.
+    CloneScratch,
+    /// Scan data with a database or stream.
+    Scan,
+    /// Open a stream.
+    OpenStream,
+    /// Close a stream.
+    CloseStream,
+    /// Reset a stream.
+    ResetStream,
+    /// Compress a stream.
+    CompressStream,
+    /// Expand a compressed stream.
+    ExpandStream,
+    /// Serialize a database.
+    Serialize,
+    /// Deserialize a database.
+    Deserialize,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match *self {
+                Operation::Compile => "compile",
+                Operation::AllocScratch => "alloc_scratch",
+                Operation::CloneScratch => "clone_scratch",
+                Operation::Scan => "scan",
+                Operation::OpenStream => "open_stream",
+                Operation::CloseStream => "close_stream",
+                Operation::ResetStream => "reset_stream",
+                Operation::CompressStream => "compress_stream",
+                Operation::ExpandStream => "expand_stream",
+                Operation::Serialize => "serialize",
+                Operation::Deserialize => "deserialize",
+            }
+        )
+    }
+}
+
+impl Error {
+    /// Record the operation which failed with the error.
+    ///
+    /// `Error::ScanTerminated` is kept as is, since it is requested by the callback instead of a failure.
+    pub fn with_operation(self, op: Operation) -> Error {
+        match self {
+            Error::ScanTerminated | Error::Operation(..) => self,
+            err => Error::Operation(op, Box::new(err)),
+        }
+    }
+
+    /// The operation which failed, if known.
+    pub fn operation(&self) -> Option<Operation> {
+        match *self {
+            Error::Operation(op, _) => Some(op),
+            _ => None,
+        }
+    }
+
+    /// The error without the operation context.
+    pub fn kind(&self) -> &Error {
+        match *self {
+            Error::Operation(_, ref err) => err.kind(),
+            _ => self,
+        }
+    }
+}
+
 impl From<i32> for Error {
     fn from(err: i32) -> Error {
         match err {
@@ -56,6 +221,9 @@ impl From<i32> for Error {
             HS_DB_MODE_ERROR => Error::DbModeError,
             HS_BAD_ALIGN => Error::BadAlign,
             HS_BAD_ALLOC => Error::BadAlloc,
+            HS_SCRATCH_IN_USE => Error::ScratchInUse,
+            HS_ARCH_ERROR => Error::ArchError,
+            HS_INSUFFICIENT_SPACE => Error::InsufficientSpace,
             _ => Error::Failed(err),
         }
     }
@@ -74,10 +242,22 @@ impl From<::std::ffi::NulError> for Error {
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Error::Operation(op, ref err) = *self {
+            return write!(f, "{} failed, {}", op, err);
+        }
+        if let Error::LineError(line, ref err) = *self {
+            return write!(f, "line {}, {}", line, err);
+        }
+
         try!(write!(f, "{}", error::Error::description(self).to_string()));
 
         match *self {
             Error::CompilerError(ref reason) => try!(write!(f, " {}", reason)),
+            Error::ExpressionError(ref err) => try!(write!(f, " {}", err)),
+            Error::DataTooLarge(ref len) => try!(write!(f, " Length: {}", len)),
+            Error::TooManySegments(ref count) => try!(write!(f, " Segments: {}", count)),
+            Error::ContainerError(ref reason) => try!(write!(f, " {}", reason)),
+            Error::IntegrityError(ref err) => try!(write!(f, " {}", err)),
             Error::Failed(ref code) => try!(write!(f, " Code: {}", code)),
             _ => {}
         }
@@ -93,36 +273,66 @@ impl error::Error for Error {
             Error::NoMem => "A memory allocation failed.",
             Error::ScanTerminated => "The engine was terminated by callback.",
             Error::CompilerError(..) => "The pattern compiler failed.",
+            Error::ExpressionError(..) => "The pattern compiler failed on an expression.",
             Error::DbVersionError => "The given database was built for a different version of Hyperscan.",
             Error::DbPlatformError => "The given database was built for a different platform.",
             Error::DbModeError => "The given database was built for a different mode of operation.",
             Error::BadAlign => "A parameter passed to this function was not correctly aligned.",
             Error::BadAlloc => "The memory allocator did not correctly return memory suitably aligned.",
+            Error::ScratchInUse => "The scratch region was already in use.",
+            Error::ArchError => "Unsupported CPU architecture.",
+            Error::InsufficientSpace => "Provided buffer was too small.",
+            Error::DataTooLarge(..) => "The data is too large to be scanned in one call.",
+            Error::TooManySegments(..) => "There are too many data segments to be scanned in one call.",
+            Error::ContainerError(..) => "The database container is malformed.",
+            Error::IntegrityError(..) => "The integrity check failed.",
             Error::Failed(..) => "Internal operation failed.",
+            Error::Operation(_, ref err) => err.description(),
+            Error::LineError(_, ref err) => err.description(),
             Error::ParseError(ref err) => err.description(),
             Error::NulError(ref err) => err.description(),
         }
     }
+
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Operation(_, ref err) => Some(&**err),
+            Error::LineError(_, ref err) => Some(&**err),
+            Error::ParseError(ref err) => Some(err),
+            Error::NulError(ref err) => Some(err),
+            _ => None,
+        }
+    }
 }
 
 macro_rules! check_hs_error {
     ($expr:expr) => {
-        if $expr != $crate::HS_SUCCESS {
-            return ::std::result::Result::Err(::std::convert::From::from($expr));
+        match $expr {
+            $crate::HS_SUCCESS => {}
+            code => return ::std::result::Result::Err(::std::convert::From::from(code)),
+        }
+    };
+    ($expr:expr, $op:expr) => {
+        match $expr {
+            $crate::HS_SUCCESS => {}
+            code => return ::std::result::Result::Err($crate::errors::Error::from(code).with_operation($op)),
         }
     };
 }
 
 macro_rules! assert_hs_error {
     ($expr:expr) => {
-        if $expr != $crate::HS_SUCCESS {
-            panic!("panic, err={}", $expr);
+        match $expr {
+            $crate::HS_SUCCESS => {}
+            code => panic!("panic, err={}", code),
         }
     };
 }
 
 pub trait CompileError: ToString {
-    fn expression(&self) -> usize;
+    /// The zero-based number of the expression that caused the error,
+    /// or `None` if the error is not specific to an expression.
+    fn expression(&self) -> Option<usize>;
 }
 
 pub type RawCompileErrorPtr = *mut hs_compile_error_t;
@@ -136,10 +346,41 @@ impl fmt::Debug for RawCompileError {
     }
 }
 
+impl RawCompileError {
+    /// Convert the compile error to `Error`,
+    /// resolving the ID and text of the offending expression with the `lookup` function.
+    pub fn into_error<F>(self, lookup: F) -> Error
+    where
+        F: FnOnce(usize) -> (Option<usize>, Option<String>),
+    {
+        let message = self.to_string();
+
+        match self.expression() {
+            Some(index) => {
+                let (id, expression) = lookup(index);
+
+                Error::ExpressionError(ExpressionError {
+                    message,
+                    index,
+                    id,
+                    expression,
+                })
+            }
+            None => Error::CompilerError(message),
+        }
+    }
+}
+
 impl CompileError for RawCompileError {
     #[inline]
-    fn expression(&self) -> usize {
-        unsafe { (*self.0).expression as usize }
+    fn expression(&self) -> Option<usize> {
+        let expression = unsafe { (*self.0).expression };
+
+        if expression < 0 {
+            None
+        } else {
+            Some(expression as usize)
+        }
     }
 }
 
@@ -163,15 +404,17 @@ impl Drop for RawCompileError {
 
 macro_rules! check_compile_error {
     ($expr:expr, $err:ident) => {
-        if $crate::HS_SUCCESS != $expr {
-            return match $expr {
-                $crate::HS_COMPILER_ERROR => {
-                    let msg = $crate::errors::RawCompileError($err);
-
-                    Err($crate::errors::Error::CompilerError(msg.to_string()))
-                }
-                _ => Err(::std::convert::From::from($expr)),
-            };
+        check_compile_error!($expr, $err, |_| (None, None))
+    };
+    ($expr:expr, $err:ident, $lookup:expr) => {
+        match $expr {
+            $crate::HS_SUCCESS => {}
+            $crate::HS_COMPILER_ERROR => {
+                return Err($crate::errors::RawCompileError($err).into_error($lookup));
+            }
+            code => {
+                return Err($crate::errors::Error::from(code).with_operation($crate::errors::Operation::Compile));
+            }
         }
     };
 }
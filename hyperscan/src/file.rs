@@ -0,0 +1,210 @@
+use std::io::{self, BufRead, Write};
+
+use compile::{Pattern, Patterns};
+use errors::Error;
+
+/// A pattern with the line it was read from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourcePattern {
+    /// The one-based line number of the pattern.
+    pub line: usize,
+    /// The parsed pattern.
+    pub pattern: Pattern,
+}
+
+/// An iterator over the patterns of a pattern file.
+#[derive(Debug)]
+pub struct PatternReader<R> {
+    lines: io::Lines<R>,
+    line: usize,
+}
+
+impl<R: BufRead> PatternReader<R> {
+    /// Constructs a reader of the pattern file.
+    pub fn new(r: R) -> PatternReader<R> {
+        PatternReader {
+            lines: r.lines(),
+            line: 0,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for PatternReader<R> {
+    type Item = io::Result<SourcePattern>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for res in &mut self.lines {
+            self.line += 1;
+
+            let text = match res {
+                Ok(text) => text,
+                Err(err) => return Some(Err(err)),
+            };
+
+            if let Some(res) = parse_line(self.line, &text) {
+                return Some(res.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)));
+            }
+        }
+
+        None
+    }
+}
+
+/// Parse a line of the pattern file, or `None` for a blank or comment line.
+fn parse_line(line: usize, text: &str) -> Option<Result<SourcePattern, Error>> {
+    let text = text.trim();
+
+    if text.is_empty() || text.starts_with('#') {
+        return None;
+    }
+
+    Some(
+        Pattern::parse(text)
+            .map(|pattern| SourcePattern { line, pattern })
+            .map_err(|err| Error::LineError(line, Box::new(err))),
+    )
+}
+
+/// Read and write the patterns in the pattern file format, which is compatible with the `hscheck` tool.
+///
+/// Each line holds a pattern in the `id:/expression/flags{ext}` format, for example
+///
+/// ```text
+/// # comments and blank lines are ignored
+/// 1:/foo.*bar/is
+/// 2:/a\.b/{min_offset=10,edit_distance=1}
+/// ```
+///
+/// The expression is written verbatim, it ends at the last `/` of the line.
+/// The flags are the characters of `CompileFlags`, and the optional extended parameters
+/// are the `min_offset`, `max_offset`, `min_length`, `edit_distance` and `hamming_distance` of `Pattern`.
+pub trait PatternFile: Sized {
+    /// Parse the patterns from the content of a pattern file.
+    ///
+    /// The error of a malformed pattern is a `Error::LineError` with the line number.
+    fn parse_file(s: &str) -> Result<Self, Error>;
+
+    /// Read the patterns from a pattern file.
+    ///
+    /// The error of a malformed pattern is an `InvalidData` error wrapping a `Error::LineError`.
+    fn read_from<R: BufRead>(r: R) -> io::Result<Self>;
+
+    /// Write the patterns to a pattern file, one pattern per line.
+    ///
+    /// The expressions which contain a line break can't be written.
+    fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()>;
+}
+
+impl PatternFile for Patterns {
+    fn parse_file(s: &str) -> Result<Self, Error> {
+        s.lines()
+            .enumerate()
+            .filter_map(|(i, text)| parse_line(i + 1, text))
+            .map(|res| res.map(|source| source.pattern))
+            .collect()
+    }
+
+    fn read_from<R: BufRead>(r: R) -> io::Result<Self> {
+        PatternReader::new(r)
+            .map(|res| res.map(|source| source.pattern))
+            .collect()
+    }
+
+    fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        for pattern in self {
+            if pattern.expression.contains(&['\n', '\r'][..]) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("pattern #{} contains a line break", pattern.id),
+                ));
+            }
+
+            try!(writeln!(w, "{}", pattern));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    extern crate env_logger;
+
+    use std::io;
+
+    use super::super::*;
+
+    const PATTERN_FILE: &str = r#"# test patterns
+1:/a.b/i
+
+  # indented comment
+2:/foo\/bar|a{2,3}/sPL
+3:/test/{min_offset=10,edit_distance=1}
+4:/#1:/
+"#;
+
+    #[test]
+    fn test_read_patterns() {
+        let _ = env_logger::try_init();
+
+        let patterns = PatternReader::new(PATTERN_FILE.as_bytes())
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            patterns.iter().map(|source| source.line).collect::<Vec<_>>(),
+            vec![2, 5, 6, 7]
+        );
+        assert_eq!(patterns[0].pattern.expression, "a.b");
+        assert_eq!(patterns[1].pattern.expression, "foo\\/bar|a{2,3}");
+        assert_eq!(
+            patterns[1].pattern.flags,
+            CompileFlags(HS_FLAG_DOTALL | HS_FLAG_PREFILTER | HS_FLAG_SOM_LEFTMOST)
+        );
+        assert_eq!(patterns[2].pattern.min_offset, Some(10));
+        assert_eq!(patterns[2].pattern.edit_distance, Some(1));
+        assert_eq!(patterns[3].pattern.expression, "#1:");
+    }
+
+    #[test]
+    fn test_pattern_file_round_trip() {
+        let _ = env_logger::try_init();
+
+        let patterns = Patterns::parse_file(PATTERN_FILE).unwrap();
+        let mut buf = vec![];
+
+        patterns.write_to(&mut buf).unwrap();
+
+        assert_eq!(
+            String::from_utf8(buf.clone()).unwrap(),
+            "1:/a.b/i\n2:/foo\\/bar|a{2,3}/sPL\n3:/test/{min_offset=10,edit_distance=1}\n4:/#1:/\n"
+        );
+        assert_eq!(Patterns::read_from(buf.as_slice()).unwrap(), patterns);
+
+        let _: BlockDatabase = patterns[..1].to_vec().build().unwrap();
+    }
+
+    #[test]
+    fn test_pattern_file_error() {
+        let _ = env_logger::try_init();
+
+        match Patterns::parse_file("1:/foo/\n\n3:/bar/x\n") {
+            Err(Error::LineError(line, err)) => {
+                assert_eq!(line, 3);
+                assert_eq!(*err, Error::CompilerError("invalid compile flag: x".to_owned()));
+            }
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        assert_eq!(
+            Patterns::read_from("1:/foo/{max_offset=-1}".as_bytes())
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidData
+        );
+
+        let mut buf = vec![];
+
+        assert!(patterns!(["foo\nbar"]).write_to(&mut buf).is_err());
+    }
+}
@@ -0,0 +1,414 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+use api::*;
+use common::StreamingDatabase;
+use errors::Error;
+use runtime::RawStream;
+
+/// The stream of a flow, and when it was last seen.
+struct Flow<'a> {
+    stream: RawStream<'a>,
+    last_seen: Instant,
+    tick: u64,
+}
+
+/// A table of streams keyed by flow.
+///
+/// The stream of a flow is opened on demand when the flow is scanned the first time.
+/// Flows which are idle for longer than the idle timeout,
+/// or the least recently seen flows when the table is full, are closed,
+/// so their matches at end of data (EOD) are reported.
+///
+/// The streams still in the table are closed without reporting any matches when it is dropped.
+pub struct StreamTable<'a, K> {
+    db: &'a StreamingDatabase,
+    flags: StreamFlags,
+    idle_timeout: Option<Duration>,
+    max_flows: Option<usize>,
+    flows: HashMap<K, Flow<'a>>,
+    lru: BTreeMap<u64, K>,
+    tick: u64,
+}
+
+impl<'a, K> fmt::Debug for StreamTable<'a, K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "StreamTable{{db: {:p}, flows: {}, idle_timeout: {:?}, max_flows: {:?}}}",
+            **self.db,
+            self.flows.len(),
+            self.idle_timeout,
+            self.max_flows
+        )
+    }
+}
+
+impl<'a, K> StreamTable<'a, K>
+where
+    K: Hash + Eq + Clone,
+{
+    /// Constructs an empty stream table for the database.
+    pub fn new(db: &'a StreamingDatabase) -> Self {
+        StreamTable {
+            db,
+            flags: 0,
+            idle_timeout: None,
+            max_flows: None,
+            flows: HashMap::new(),
+            lru: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    /// Flags used to open the streams.
+    pub fn with_flags(mut self, flags: StreamFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Close the flows which were not seen for longer than the timeout.
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Close the least recently seen flow when opening a flow would exceed the limit.
+    ///
+    /// Panics if the limit is zero, since no flow could be opened.
+    pub fn with_max_flows(mut self, max_flows: usize) -> Self {
+        assert!(max_flows > 0, "a stream table must hold at least one flow");
+
+        self.max_flows = Some(max_flows);
+        self
+    }
+
+    /// The database which the streams were opened for.
+    pub fn database(&self) -> &'a StreamingDatabase {
+        self.db
+    }
+
+    /// The number of open flows.
+    pub fn len(&self) -> usize {
+        self.flows.len()
+    }
+
+    /// Whether there is no open flow.
+    pub fn is_empty(&self) -> bool {
+        self.flows.is_empty()
+    }
+
+    /// Whether the flow is open.
+    pub fn contains(&self, key: &K) -> bool {
+        self.flows.contains_key(key)
+    }
+
+    /// Open the stream of a flow if it is not open yet, and mark it as seen.
+    ///
+    /// Idle flows are closed, and the least recently seen flows are closed when the table is full.
+    /// Returns whether the stream was opened.
+    pub fn open<S, F>(&mut self, key: K, scratch: &S, mut on_match: F) -> Result<bool, Error>
+    where
+        S: Scratch,
+        F: FnMut(&K, Match) -> Matching,
+    {
+        let opened = !self.flows.contains_key(&key);
+
+        try!(self.touch(&key, scratch, &mut on_match));
+
+        Ok(opened)
+    }
+
+    /// Scan the data of a flow, opening its stream if it is not open yet.
+    ///
+    /// Idle flows are closed, and the least recently seen flows are closed when the table is full.
+    /// All matches are reported with the key of the flow they belong to.
+    pub fn scan<T, S, F>(&mut self, key: K, data: T, scratch: &S, mut on_match: F) -> Result<(), Error>
+    where
+        T: Scannable,
+        S: Scratch,
+        F: FnMut(&K, Match) -> Matching,
+    {
+        try!(self.touch(&key, scratch, &mut on_match));
+
+        self.flows[&key]
+            .stream
+            .scan(data, 0, scratch, |m| on_match(&key, m))
+            .map(|_| ())
+    }
+
+    /// Expire the idle flows, and open the stream of the flow or mark it as the most recently seen.
+    fn touch<S, F>(&mut self, key: &K, scratch: &S, on_match: &mut F) -> Result<(), Error>
+    where
+        S: Scratch,
+        F: FnMut(&K, Match) -> Matching,
+    {
+        let now = Instant::now();
+
+        try!(self.expire_at(now, scratch, on_match));
+
+        self.tick += 1;
+
+        let tick = self.tick;
+
+        if let Some(flow) = self.flows.get_mut(key) {
+            self.lru.remove(&flow.tick);
+
+            flow.last_seen = now;
+            flow.tick = tick;
+        } else {
+            if let Some(max_flows) = self.max_flows {
+                while self.flows.len() >= max_flows {
+                    match self.lru.values().next().cloned() {
+                        Some(oldest) => {
+                            trace!("evict the least recently seen flow");
+
+                            try!(self.close_flow(&oldest, scratch, on_match));
+                        }
+                        None => break,
+                    }
+                }
+            }
+
+            let stream = try!(self.db.open_stream(self.flags));
+
+            self.flows.insert(
+                key.clone(),
+                Flow {
+                    stream,
+                    last_seen: now,
+                    tick,
+                },
+            );
+        }
+
+        self.lru.insert(tick, key.clone());
+
+        Ok(())
+    }
+
+    /// Close the flows which are idle for longer than the idle timeout.
+    ///
+    /// Returns the number of closed flows.
+    pub fn expire<S, F>(&mut self, scratch: &S, mut on_match: F) -> Result<usize, Error>
+    where
+        S: Scratch,
+        F: FnMut(&K, Match) -> Matching,
+    {
+        self.expire_at(Instant::now(), scratch, &mut on_match)
+    }
+
+    fn expire_at<S, F>(&mut self, now: Instant, scratch: &S, on_match: &mut F) -> Result<usize, Error>
+    where
+        S: Scratch,
+        F: FnMut(&K, Match) -> Matching,
+    {
+        let timeout = match self.idle_timeout {
+            Some(timeout) => timeout,
+            None => return Ok(0),
+        };
+
+        let mut expired = 0;
+
+        while let Some(key) = self.lru.values().next().cloned() {
+            if now.duration_since(self.flows[&key].last_seen) < timeout {
+                break;
+            }
+
+            try!(self.close_flow(&key, scratch, on_match));
+
+            expired += 1;
+        }
+
+        if expired > 0 {
+            trace!("expired {} idle flows", expired);
+        }
+
+        Ok(expired)
+    }
+
+    /// Close a flow, reporting its matches at end of data (EOD).
+    ///
+    /// Returns whether the flow was open.
+    pub fn close<S, F>(&mut self, key: &K, scratch: &S, mut on_match: F) -> Result<bool, Error>
+    where
+        S: Scratch,
+        F: FnMut(&K, Match) -> Matching,
+    {
+        self.close_flow(key, scratch, &mut on_match)
+    }
+
+    /// Close all flows, reporting their matches at end of data (EOD).
+    pub fn close_all<S, F>(&mut self, scratch: &S, mut on_match: F) -> Result<(), Error>
+    where
+        S: Scratch,
+        F: FnMut(&K, Match) -> Matching,
+    {
+        while let Some(key) = self.lru.values().next().cloned() {
+            try!(self.close_flow(&key, scratch, &mut on_match));
+        }
+
+        Ok(())
+    }
+
+    /// Reset the streams of all flows to an initial state, reporting their matches at end of data (EOD).
+    pub fn reset_all<S, F>(&mut self, scratch: &S, mut on_match: F) -> Result<(), Error>
+    where
+        S: Scratch,
+        F: FnMut(&K, Match) -> Matching,
+    {
+        for (key, flow) in &self.flows {
+            try!(flow.stream.reset(self.flags, scratch, |m| on_match(key, m)));
+        }
+
+        Ok(())
+    }
+
+    fn close_flow<S, F>(&mut self, key: &K, scratch: &S, on_match: &mut F) -> Result<bool, Error>
+    where
+        S: Scratch,
+        F: FnMut(&K, Match) -> Matching,
+    {
+        let tick = match self.flows.get(key) {
+            Some(flow) => {
+                // report the matches at EOD before removing the flow, so it stays in the table if that fails
+                try!(flow.stream.reset(self.flags, scratch, |m| on_match(key, m)));
+
+                flow.tick
+            }
+            None => return Ok(false),
+        };
+
+        self.lru.remove(&tick);
+
+        // the stream is closed without reporting any matches when it is dropped
+        self.flows.remove(key);
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    extern crate env_logger;
+
+    use std::time::Duration;
+
+    use super::super::*;
+
+    #[test]
+    fn test_stream_table() {
+        let _ = env_logger::try_init();
+
+        let db: StreamingDatabase = patterns!(["foobar", "end$"]).build().unwrap();
+        let s = db.alloc().unwrap();
+
+        let mut table = StreamTable::new(&db).with_max_flows(2);
+        let mut matches = vec![];
+
+        {
+            let mut on_match = |key: &&'static str, m: Match| {
+                matches.push((*key, m.id, m.to));
+
+                Matching::Continue
+            };
+
+            table.scan("a", "foo", &s, &mut on_match).unwrap();
+            table.scan("b", "the end", &s, &mut on_match).unwrap();
+            table.scan("a", "bar", &s, &mut on_match).unwrap();
+
+            assert_eq!(table.len(), 2);
+
+            // `b` is the least recently seen flow
+            table.scan("c", "foo", &s, &mut on_match).unwrap();
+
+            assert!(!table.contains(&"b"));
+            assert!(table.contains(&"a"));
+            assert!(table.contains(&"c"));
+
+            assert!(table.close(&"a", &s, &mut on_match).unwrap());
+            assert!(!table.close(&"a", &s, &mut on_match).unwrap());
+
+            table.close_all(&s, &mut on_match).unwrap();
+        }
+
+        assert!(table.is_empty());
+        assert_eq!(matches, vec![("a", 1, 6), ("b", 2, 7)]);
+    }
+
+    #[test]
+    fn test_stream_table_open() {
+        let _ = env_logger::try_init();
+
+        let db: StreamingDatabase = patterns!(["foobar", "end$"]).build().unwrap();
+        let s = db.alloc().unwrap();
+
+        let mut table = StreamTable::new(&db);
+        let mut matches = vec![];
+
+        {
+            let mut on_match = |key: &u32, m: Match| {
+                matches.push((*key, m.id, m.to));
+
+                Matching::Continue
+            };
+
+            assert!(table.open(1, &s, &mut on_match).unwrap());
+            assert!(!table.open(1, &s, &mut on_match).unwrap());
+            assert!(table.contains(&1));
+
+            table.scan(1, "foobar, the end", &s, &mut on_match).unwrap();
+            table.close_all(&s, &mut on_match).unwrap();
+        }
+
+        assert!(table.is_empty());
+        assert_eq!(matches, vec![(1, 1, 6), (1, 2, 15)]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_stream_table_no_flows() {
+        let _ = env_logger::try_init();
+
+        let db: StreamingDatabase = pattern! {"test"}.build().unwrap();
+
+        let _: StreamTable<u32> = StreamTable::new(&db).with_max_flows(0);
+    }
+
+    #[test]
+    fn test_stream_table_idle_timeout() {
+        let _ = env_logger::try_init();
+
+        let db: StreamingDatabase = pattern! {"end$"}.build().unwrap();
+        let s = db.alloc().unwrap();
+
+        let mut table = StreamTable::new(&db).with_idle_timeout(Duration::from_secs(0));
+        let mut matches = vec![];
+
+        table
+            .scan(1, "the end", &s, |key: &u32, m: Match| {
+                matches.push((*key, m.to));
+
+                Matching::Continue
+            })
+            .unwrap();
+
+        assert_eq!(table.len(), 1);
+
+        assert_eq!(
+            table
+                .expire(&s, |key: &u32, m: Match| {
+                    matches.push((*key, m.to));
+
+                    Matching::Continue
+                })
+                .unwrap(),
+            1
+        );
+
+        assert!(table.is_empty());
+        assert_eq!(matches, vec![(1, 7)]);
+    }
+}
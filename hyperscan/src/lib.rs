@@ -8,30 +8,33 @@
 //!
 //! use hyperscan::*;
 //!
-//! fn callback(id: u32, from: u64, to: u64, flags: u32, _: &BlockDatabase) -> u32 {
-//!     assert_eq!(id, 0);
-//!     assert_eq!(from, 5);
-//!     assert_eq!(to, 9);
-//!     assert_eq!(flags, 0);
-//!
-//!     println!("found pattern #{} @ [{}, {})", id, from, to);
-//!
-//!     0
-//! }
-//!
 //! fn main() {
 //!     let pattern = &pattern!{"test", flags => HS_FLAG_CASELESS|HS_FLAG_SOM_LEFTMOST};
 //!     let db: BlockDatabase = pattern.build().unwrap();
 //!     let scratch = db.alloc().unwrap();
 //!
-//!     db.scan::<BlockDatabase>("some test data", 0, &scratch, Some(callback), Some(&db)).unwrap();
+//!     db.scan("some test data", 0, &scratch, |m: Match| {
+//!         assert_eq!(m.id, 0);
+//!         assert_eq!(m.from, 5);
+//!         assert_eq!(m.to, 9);
+//!         assert_eq!(m.flags, 0);
+//!
+//!         println!("found pattern #{} @ [{}, {})", m.id, m.from, m.to);
+//!
+//!         Matching::Continue
+//!     }).unwrap();
 //! }
 //! ```
 
 #[macro_use]
 extern crate log;
+extern crate hmac;
 extern crate libc;
 extern crate regex_syntax;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+extern crate sha2;
 
 extern crate hyperscan_sys as raw;
 
@@ -40,19 +43,53 @@ mod cptr;
 #[macro_use]
 mod errors;
 mod api;
+mod bisect;
+mod cache;
 mod common;
 #[macro_use]
 mod compile;
+mod container;
+mod envelope;
+mod file;
+mod flow;
+mod lint;
+mod literal;
+mod logical;
+mod pool;
+mod prefilter;
+mod profile;
 mod runtime;
+mod shard;
 
 pub use api::*;
-pub use common::{BlockDatabase, RawDatabase, StreamingDatabase, VectoredDatabase};
+pub use bisect::{Bisect, BisectReport, Culprit};
+pub use cache::{CacheStats, DatabaseCache};
+pub use common::{
+    AnyDatabase, BlockDatabase, DatabaseBuffer, DatabaseRef, RawDatabase, StreamingDatabase, VectoredDatabase,
+};
 pub use compile::{CompileFlags, Pattern, Patterns};
 pub use constants::*;
-pub use errors::Error;
+pub use container::DatabaseContainer;
+pub use envelope::Envelope;
+pub use errors::{Error, ExpressionError, IntegrityError, Operation};
+pub use file::{PatternFile, PatternReader, SourcePattern};
+pub use flow::StreamTable;
+pub use lint::{Finding, Lint, Linter, Severity};
+pub use literal::{Literal, Literals};
+pub use logical::{Combination, Combinations, Logical};
+pub use pool::{PooledScratch, ScratchPool};
+#[cfg(feature = "fancy-regex")]
+pub use prefilter::FancyRegexVerifier;
+#[cfg(feature = "regex")]
+pub use prefilter::RegexVerifier;
+pub use prefilter::{PrefilterDatabase, Verifier};
+pub use profile::{PatternCost, Profile, ProfileReport};
 pub use runtime::{RawScratch, RawStream};
+pub use shard::{ShardedDatabase, ShardedScratch, ShardedStream};
 
-#[cfg(test)]
+#[cfg(feature = "fancy-regex")]
+extern crate fancy_regex;
+#[cfg(any(test, feature = "regex"))]
 extern crate regex;
 
 #[cfg(test)]
@@ -0,0 +1,431 @@
+use std::fmt;
+
+use regex_syntax::ast::{self, AssertionKind, Ast, RepetitionKind, RepetitionRange};
+use regex_syntax::ParserBuilder;
+
+use api::*;
+use compile::Pattern;
+use constants::*;
+
+/// The default threshold of large bounded repeats.
+const DEFAULT_MAX_REPEAT: u32 = 256;
+
+/// The `max_width` reported by Hyperscan for the patterns without an upper bound.
+const UNBOUNDED_WIDTH: usize = u32::MAX as usize;
+
+/// The severity of a lint finding.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The pattern could be simplified.
+    Info,
+    /// The pattern is likely a mistake or a performance problem.
+    Warning,
+    /// The pattern fails to compile.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match *self {
+                Severity::Info => "info",
+                Severity::Warning => "warning",
+                Severity::Error => "error",
+            }
+        )
+    }
+}
+
+/// The kind of a lint finding.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Lint {
+    /// The pattern fails to compile.
+    Invalid,
+    /// The pattern has no upper bound of its match width.
+    UnboundedWidth,
+    /// The pattern can match an empty buffer, but `HS_FLAG_ALLOWEMPTY` isn't set.
+    EmptyMatch,
+    /// The pattern has a large bounded repeat.
+    LargeRepeat,
+    /// The pattern starts with a `.*`, which has no effect since Hyperscan reports all matches.
+    LeadingDotStar,
+    /// The pattern can only match at the end of data, which is the end of every block in block mode.
+    OnlyAtEod,
+    /// The flag has no effect on the pattern.
+    RedundantFlag,
+}
+
+/// A lint finding of a pattern.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    /// ID number of the pattern.
+    pub id: usize,
+    /// The kind of the finding.
+    pub lint: Lint,
+    /// The severity of the finding.
+    pub severity: Severity,
+    /// A human-readable message describing the finding.
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: pattern #{}: {}", self.severity, self.id, self.message)
+    }
+}
+
+/// A lint pass over patterns, which flags likely mistakes and performance problems.
+///
+/// The syntax of the patterns is analyzed with `regex-syntax`,
+/// and their match widths and end of data (EOD) behaviour with `Expression::info`.
+#[derive(Debug, Clone)]
+pub struct Linter {
+    mode: u32,
+    max_repeat: u32,
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Linter {
+            mode: HS_MODE_BLOCK,
+            max_repeat: DEFAULT_MAX_REPEAT,
+        }
+    }
+}
+
+impl Linter {
+    /// Constructs a linter for the block mode.
+    pub fn new() -> Linter {
+        Linter::default()
+    }
+
+    /// The mode which the patterns will be compiled in.
+    pub fn with_mode(mut self, mode: u32) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// The repeat count above which a bounded repeat is flagged.
+    pub fn with_max_repeat(mut self, max_repeat: u32) -> Self {
+        self.max_repeat = max_repeat;
+        self
+    }
+
+    /// Lint the patterns, the findings are ordered by the patterns.
+    pub fn lint(&self, patterns: &[Pattern]) -> Vec<Finding> {
+        patterns.iter().flat_map(|pattern| self.lint_pattern(pattern)).collect()
+    }
+
+    /// Lint a pattern.
+    pub fn lint_pattern(&self, pattern: &Pattern) -> Vec<Finding> {
+        let mut findings = vec![];
+
+        {
+            let mut report = |lint, severity, message: String| {
+                findings.push(Finding {
+                    id: pattern.id,
+                    lint,
+                    severity,
+                    message,
+                })
+            };
+
+            let syntax = ast::parse::Parser::new().parse(&pattern.expression).ok();
+
+            match syntax {
+                Some(ref ast) => self.lint_syntax(pattern, ast, &mut report),
+                None => trace!("skip the syntax lints of pattern `{}`", pattern),
+            }
+
+            let match_empty = ParserBuilder::new()
+                .allow_invalid_utf8(true)
+                .build()
+                .parse(&pattern.expression)
+                .map(|hir| hir.is_match_empty())
+                .ok();
+            let empty_match = match_empty == Some(true) && !pattern.flags.is_set(HS_FLAG_ALLOWEMPTY);
+
+            if empty_match {
+                report(
+                    Lint::EmptyMatch,
+                    Severity::Error,
+                    "pattern can match an empty buffer without HS_FLAG_ALLOWEMPTY".to_owned(),
+                );
+            }
+            if match_empty == Some(false) && pattern.flags.is_set(HS_FLAG_ALLOWEMPTY) {
+                report(
+                    Lint::RedundantFlag,
+                    Severity::Info,
+                    "HS_FLAG_ALLOWEMPTY has no effect on a pattern which can't match empty".to_owned(),
+                );
+            }
+
+            match pattern.info() {
+                Ok(info) => {
+                    if info.max_width == UNBOUNDED_WIDTH {
+                        report(
+                            Lint::UnboundedWidth,
+                            Severity::Warning,
+                            "pattern has no upper bound of its match width".to_owned(),
+                        );
+                    }
+
+                    if info.matches_only_at_eod && self.mode & (HS_MODE_BLOCK | HS_MODE_VECTORED) != 0 {
+                        report(
+                            Lint::OnlyAtEod,
+                            Severity::Warning,
+                            "pattern can only match at the end of each block".to_owned(),
+                        );
+                    }
+                }
+                Err(_) if empty_match => {}
+                Err(err) => report(Lint::Invalid, Severity::Error, err.to_string()),
+            }
+        }
+
+        findings
+    }
+
+    fn lint_syntax<F>(&self, pattern: &Pattern, ast: &Ast, report: &mut F)
+    where
+        F: FnMut(Lint, Severity, String),
+    {
+        if let Some(Ast::Repetition(rep)) = leading_item(ast) {
+            if rep.op.kind == RepetitionKind::ZeroOrMore && matches!(*rep.ast, Ast::Dot(_)) {
+                report(
+                    Lint::LeadingDotStar,
+                    Severity::Info,
+                    "leading `.*` has no effect and slows down the scan".to_owned(),
+                );
+            }
+        }
+
+        let stats = ast::visit(
+            ast,
+            SyntaxStats {
+                max_repeat: self.max_repeat,
+                ..Default::default()
+            },
+        )
+        .unwrap_or_default();
+
+        for n in &stats.large_repeats {
+            report(
+                Lint::LargeRepeat,
+                Severity::Warning,
+                format!("bounded repeat of {} exceeds {}", n, self.max_repeat),
+            );
+        }
+
+        let flags = pattern.flags;
+
+        if flags.is_set(HS_FLAG_CASELESS) && !stats.has_cased {
+            report(
+                Lint::RedundantFlag,
+                Severity::Info,
+                "HS_FLAG_CASELESS has no effect without letters".to_owned(),
+            );
+        }
+        if flags.is_set(HS_FLAG_DOTALL) && !stats.has_dot {
+            report(
+                Lint::RedundantFlag,
+                Severity::Info,
+                "HS_FLAG_DOTALL has no effect without `.`".to_owned(),
+            );
+        }
+        if flags.is_set(HS_FLAG_MULTILINE) && !stats.has_line_anchor {
+            report(
+                Lint::RedundantFlag,
+                Severity::Info,
+                "HS_FLAG_MULTILINE has no effect without `^` or `$`".to_owned(),
+            );
+        }
+    }
+}
+
+/// The leading item of the expression, looking through the non-capturing groups and the flags.
+fn leading_item(ast: &Ast) -> Option<&Ast> {
+    match *ast {
+        Ast::Concat(ref concat) => concat
+            .asts
+            .iter()
+            .find(|ast| !matches!(ast, Ast::Flags(_) | Ast::Empty(_)))
+            .and_then(leading_item),
+        Ast::Group(ast::Group {
+            kind: ast::GroupKind::NonCapturing(_),
+            ast: ref inner,
+            ..
+        }) => leading_item(inner),
+        Ast::Flags(_) | Ast::Empty(_) => None,
+        _ => Some(ast),
+    }
+}
+
+/// The syntax features of a pattern, collected from its AST.
+#[derive(Debug, Default)]
+struct SyntaxStats {
+    max_repeat: u32,
+    large_repeats: Vec<u32>,
+    has_cased: bool,
+    has_dot: bool,
+    has_line_anchor: bool,
+}
+
+impl ast::Visitor for SyntaxStats {
+    type Output = SyntaxStats;
+    type Err = ();
+
+    fn finish(self) -> Result<Self::Output, Self::Err> {
+        Ok(self)
+    }
+
+    fn visit_pre(&mut self, ast: &Ast) -> Result<(), Self::Err> {
+        match *ast {
+            Ast::Literal(ref lit) if lit.c.is_lowercase() || lit.c.is_uppercase() => self.has_cased = true,
+            // be conservative, a class may contain letters
+            Ast::Class(_) => self.has_cased = true,
+            Ast::Dot(_) => self.has_dot = true,
+            Ast::Assertion(ref assertion)
+                if assertion.kind == AssertionKind::StartLine || assertion.kind == AssertionKind::EndLine =>
+            {
+                self.has_line_anchor = true
+            }
+            Ast::Repetition(ref rep) => {
+                let n = match rep.op.kind {
+                    RepetitionKind::Range(RepetitionRange::Exactly(n))
+                    | RepetitionKind::Range(RepetitionRange::AtLeast(n)) => n,
+                    RepetitionKind::Range(RepetitionRange::Bounded(_, n)) => n,
+                    _ => 0,
+                };
+
+                if n > self.max_repeat {
+                    self.large_repeats.push(n);
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    extern crate env_logger;
+
+    use super::super::*;
+
+    fn lints(linter: &Linter, pattern: Pattern) -> Vec<(Lint, Severity)> {
+        linter
+            .lint_pattern(&pattern)
+            .into_iter()
+            .map(|finding| (finding.lint, finding.severity))
+            .collect()
+    }
+
+    #[test]
+    fn test_lint() {
+        let _ = env_logger::try_init();
+
+        let linter = Linter::new();
+
+        assert_eq!(lints(&linter, pattern! {"test"}), vec![]);
+        assert_eq!(
+            lints(&linter, pattern! {"a+"}),
+            vec![(Lint::UnboundedWidth, Severity::Warning)]
+        );
+        assert_eq!(
+            lints(&linter, pattern! {"a*"}),
+            vec![(Lint::EmptyMatch, Severity::Error)]
+        );
+        assert_eq!(
+            lints(&linter, pattern! {"a*", flags => HS_FLAG_ALLOWEMPTY}),
+            vec![(Lint::UnboundedWidth, Severity::Warning)]
+        );
+        assert_eq!(
+            lints(&linter, pattern! {"test", flags => HS_FLAG_ALLOWEMPTY}),
+            vec![(Lint::RedundantFlag, Severity::Info)]
+        );
+        assert_eq!(
+            lints(&linter, pattern! {"a[^b]{1000}b"}),
+            vec![(Lint::LargeRepeat, Severity::Warning)]
+        );
+        assert_eq!(
+            lints(&linter, pattern! {".*foo"}),
+            vec![
+                (Lint::LeadingDotStar, Severity::Info),
+                (Lint::UnboundedWidth, Severity::Warning)
+            ]
+        );
+        assert_eq!(
+            lints(&linter, pattern! {"foo\\z"}),
+            vec![(Lint::OnlyAtEod, Severity::Warning)]
+        );
+        assert_eq!(
+            lints(&linter.clone().with_mode(HS_MODE_STREAM), pattern! {"foo\\z"}),
+            vec![]
+        );
+        assert_eq!(
+            lints(&linter, pattern! {"123", flags => HS_FLAG_CASELESS | HS_FLAG_DOTALL}),
+            vec![
+                (Lint::RedundantFlag, Severity::Info),
+                (Lint::RedundantFlag, Severity::Info)
+            ]
+        );
+        assert_eq!(
+            lints(&linter, pattern! {"foo("}),
+            vec![(Lint::Invalid, Severity::Error)]
+        );
+    }
+
+    #[test]
+    fn test_lint_leading_dot_star() {
+        let _ = env_logger::try_init();
+
+        let linter = Linter::new();
+
+        for expr in &[
+            ".*foo",
+            "(?:.*)foo",
+            "(?s).*foo",
+            "(?s:.*)foo",
+            ".*?foo",
+            "(?:(?i).*f)oo",
+        ] {
+            assert_eq!(
+                lints(&linter, pattern! {*expr}),
+                vec![
+                    (Lint::LeadingDotStar, Severity::Info),
+                    (Lint::UnboundedWidth, Severity::Warning)
+                ],
+                "pattern `{}`",
+                expr
+            );
+        }
+
+        for expr in &["(.*)foo", "(?:.*|a)foo", "foo.*bar"] {
+            assert_eq!(
+                lints(&linter, pattern! {*expr}),
+                vec![(Lint::UnboundedWidth, Severity::Warning)],
+                "pattern `{}`",
+                expr
+            );
+        }
+    }
+
+    #[test]
+    fn test_lint_patterns() {
+        let _ = env_logger::try_init();
+
+        let findings = Linter::new().lint(&patterns!(["foo", "bar+", "baz"]));
+
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].id, 2);
+        assert_eq!(
+            findings[0].to_string(),
+            "warning: pattern #2: pattern has no upper bound of its match width"
+        );
+    }
+}
@@ -0,0 +1,254 @@
+use std::ascii;
+use std::fmt;
+use std::os::raw::c_uint;
+use std::ptr;
+
+use api::*;
+use common::RawDatabase;
+use compile::CompileFlags;
+use errors::{Error, RawCompileErrorPtr};
+use raw::*;
+
+/// A pure literal, which is matched byte by byte without any regular expression syntax.
+///
+/// The literal may contain NUL bytes, and only the `HS_FLAG_CASELESS`, `HS_FLAG_SINGLEMATCH`
+/// and `HS_FLAG_SOM_LEFTMOST` flags are supported.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Literal {
+    /// The bytes to match.
+    pub expression: Vec<u8>,
+    /// Flags which modify the behaviour of the literal.
+    pub flags: CompileFlags,
+    /// ID number to be associated with the corresponding literal in the literals array.
+    pub id: usize,
+}
+
+impl Literal {
+    /// Constructs a literal of the bytes.
+    pub fn new<T: Into<Vec<u8>>>(expression: T) -> Literal {
+        Literal {
+            expression: expression.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Flags which modify the behaviour of the literal.
+    pub fn with_flags<F: Into<CompileFlags>>(mut self, flags: F) -> Self {
+        self.flags = flags.into();
+        self
+    }
+
+    /// ID number to be associated with the literal.
+    pub fn with_id(mut self, id: usize) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// The literal bytes with the non-printable bytes escaped.
+    fn escaped(&self) -> String {
+        let bytes = self
+            .expression
+            .iter()
+            .flat_map(|&b| ascii::escape_default(b))
+            .collect::<Vec<_>>();
+
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:\"{}\"{}", self.id, self.escaped(), self.flags)
+    }
+}
+
+/// Vec of `Literal`
+pub type Literals = Vec<Literal>;
+
+/// Resolve the ID and expression of the literal at the given index.
+fn lookup_literal(literals: &[Literal], index: usize) -> (Option<usize>, Option<String>) {
+    match literals.get(index) {
+        Some(literal) => (Some(literal.id), Some(literal.escaped())),
+        None => (None, None),
+    }
+}
+
+impl<T: Type> DatabaseBuilder<RawDatabase<T>> for Literal {
+    /// The pure literal expression compiler.
+    ///
+    /// The literal is compiled with `hs_compile_lit`, which requires Hyperscan 5.2 or later.
+    fn build_for_platform(&self, platform: &PlatformInfo) -> Result<RawDatabase<T>, Error> {
+        let mut db: RawDatabasePtr = ptr::null_mut();
+        let mut err: RawCompileErrorPtr = ptr::null_mut();
+
+        unsafe {
+            check_compile_error!(
+                hs_compile_lit(
+                    self.expression.as_ptr() as *const i8,
+                    self.flags.0,
+                    self.expression.len(),
+                    T::mode(),
+                    platform.as_ptr(),
+                    &mut db,
+                    &mut err
+                ),
+                err,
+                |_| (Some(self.id), Some(self.escaped()))
+            );
+        }
+
+        debug!("literal `{}` compiled to {} database {:p}", self, T::name(), db);
+
+        Ok(RawDatabase::from_raw(db))
+    }
+}
+
+impl<T: Type> DatabaseBuilder<RawDatabase<T>> for Literals {
+    /// The multiple pure literal expression compiler.
+    ///
+    /// The literals are compiled with `hs_compile_lit_multi`, which requires Hyperscan 5.2 or later.
+    fn build_for_platform(&self, platform: &PlatformInfo) -> Result<RawDatabase<T>, Error> {
+        let mut ptrs = Vec::with_capacity(self.len());
+        let mut lens = Vec::with_capacity(self.len());
+        let mut flags = Vec::with_capacity(self.len());
+        let mut ids = Vec::with_capacity(self.len());
+
+        for literal in self {
+            ptrs.push(literal.expression.as_ptr() as *const i8);
+            lens.push(literal.expression.len());
+            flags.push(literal.flags.0 as c_uint);
+            ids.push(literal.id as c_uint);
+        }
+
+        let mut db: RawDatabasePtr = ptr::null_mut();
+        let mut err: RawCompileErrorPtr = ptr::null_mut();
+
+        unsafe {
+            check_compile_error!(
+                hs_compile_lit_multi(
+                    ptrs.as_ptr(),
+                    flags.as_ptr(),
+                    ids.as_ptr(),
+                    lens.as_ptr(),
+                    self.len() as u32,
+                    T::mode(),
+                    platform.as_ptr(),
+                    &mut db,
+                    &mut err
+                ),
+                err,
+                |index| lookup_literal(self, index)
+            );
+        }
+
+        debug!("{} literals compiled to {} database {:p}", self.len(), T::name(), db);
+
+        Ok(RawDatabase::from_raw(db))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    extern crate env_logger;
+
+    use super::super::common::tests::*;
+    use super::super::*;
+
+    #[test]
+    fn test_literal() {
+        let _ = env_logger::try_init();
+
+        let literal = Literal::new(&b"foo\0bar"[..]).with_flags(HS_FLAG_CASELESS).with_id(1);
+
+        assert_eq!(literal.to_string(), "1:\"foo\\x00bar\"i");
+
+        let db: BlockDatabase = literal.build().unwrap();
+
+        validate_database(&db);
+
+        let s = db.alloc().unwrap();
+        let mut matches = vec![];
+
+        db.scan(&b"xxFOO\0BARxxfoobar"[..], 0, &s, |m: Match| {
+            matches.push((m.id, m.to));
+
+            Matching::Continue
+        })
+        .unwrap();
+
+        assert_eq!(matches, vec![(1, 9)]);
+    }
+
+    #[test]
+    fn test_literals() {
+        let _ = env_logger::try_init();
+
+        let literals = vec![
+            Literal::new("a.b").with_id(1),
+            Literal::new(&b"\0\xff"[..]).with_id(2),
+            Literal::new("(").with_id(3),
+        ];
+
+        let db: BlockDatabase = literals.build().unwrap();
+        let s = db.alloc().unwrap();
+        let mut matches = vec![];
+
+        db.scan(&b"axb a.b \0\xff ("[..], 0, &s, |m: Match| {
+            matches.push((m.id, m.to));
+
+            Matching::Continue
+        })
+        .unwrap();
+
+        assert_eq!(matches, vec![(1, 7), (2, 10), (3, 12)]);
+
+        let db: StreamingDatabase = literals.build().unwrap();
+        let s = db.alloc().unwrap();
+        let st = db.open_stream(0).unwrap();
+        let mut matches = vec![];
+
+        {
+            let mut on_match = |m: Match| {
+                matches.push((m.id, m.to));
+
+                Matching::Continue
+            };
+
+            st.scan("a.", 0, &s, &mut on_match).unwrap();
+            st.scan("b", 0, &s, &mut on_match).unwrap();
+            st.close(&s, &mut on_match).unwrap();
+        }
+
+        assert_eq!(matches, vec![(1, 3)]);
+
+        let db: VectoredDatabase = literals.build().unwrap();
+        let s = db.alloc().unwrap();
+        let mut matches = vec![];
+
+        db.scan(&["a", ".b"], 0, &s, |m: Match| {
+            matches.push((m.id, m.to));
+
+            Matching::Continue
+        })
+        .unwrap();
+
+        assert_eq!(matches, vec![(1, 3)]);
+    }
+
+    #[test]
+    fn test_literal_error() {
+        let _ = env_logger::try_init();
+
+        let literals = vec![Literal::new("foo").with_id(1), Literal::new("").with_id(2)];
+
+        let res: Result<BlockDatabase, Error> = literals.build();
+
+        match res {
+            Err(Error::ExpressionError(err)) => {
+                assert_eq!(err.index, 1);
+                assert_eq!(err.id, Some(2));
+            }
+            res => panic!("unexpected result: {:?}", res),
+        }
+    }
+}
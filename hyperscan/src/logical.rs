@@ -0,0 +1,373 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::{BitAnd, BitOr, Not};
+
+use api::*;
+use common::RawDatabase;
+use compile::{CompileFlags, Pattern, Patterns};
+use constants::*;
+use errors::{Error, ExpressionError};
+
+/// A logical expression over the ids of patterns, which is compiled as a `HS_FLAG_COMBINATION` pattern.
+///
+/// The expression could be built with the `&`, `|` and `!` operators,
+/// for example `(Logical::id(101) & Logical::id(102)) | !Logical::id(103)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Logical {
+    /// The pattern with the id has matched.
+    Id(usize),
+    /// The expression is false.
+    Not(Box<Logical>),
+    /// All the expressions are true.
+    And(Vec<Logical>),
+    /// Any of the expressions is true.
+    Or(Vec<Logical>),
+}
+
+impl Logical {
+    /// The pattern with the id has matched.
+    pub fn id(id: usize) -> Logical {
+        Logical::Id(id)
+    }
+
+    /// The ids of the patterns which are referenced by the expression, in the order of their first reference.
+    pub fn ids(&self) -> Vec<usize> {
+        let mut ids = vec![];
+
+        self.visit(&mut |id| {
+            if !ids.contains(&id) {
+                ids.push(id)
+            }
+        });
+
+        ids
+    }
+
+    fn visit<F: FnMut(usize)>(&self, f: &mut F) {
+        match *self {
+            Logical::Id(id) => f(id),
+            Logical::Not(ref expr) => expr.visit(f),
+            Logical::And(ref exprs) | Logical::Or(ref exprs) => {
+                for expr in exprs {
+                    expr.visit(f)
+                }
+            }
+        }
+    }
+
+    /// Whether any `And` or `Or` of the expression has no operand.
+    fn has_empty_operands(&self) -> bool {
+        match *self {
+            Logical::Id(_) => false,
+            Logical::Not(ref expr) => expr.has_empty_operands(),
+            Logical::And(ref exprs) | Logical::Or(ref exprs) => {
+                exprs.is_empty() || exprs.iter().any(Logical::has_empty_operands)
+            }
+        }
+    }
+
+    fn fmt_operand(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Logical::Id(_) | Logical::Not(_) => write!(f, "{}", self),
+            _ => write!(f, "({})", self),
+        }
+    }
+}
+
+impl fmt::Display for Logical {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Logical::Id(id) => write!(f, "{}", id),
+            Logical::Not(ref expr) => {
+                try!(write!(f, "!"));
+
+                expr.fmt_operand(f)
+            }
+            Logical::And(ref exprs) | Logical::Or(ref exprs) => {
+                let op = if let Logical::And(_) = *self { " & " } else { " | " };
+
+                for (i, expr) in exprs.iter().enumerate() {
+                    if i > 0 {
+                        try!(write!(f, "{}", op));
+                    }
+
+                    try!(expr.fmt_operand(f));
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl From<usize> for Logical {
+    fn from(id: usize) -> Self {
+        Logical::Id(id)
+    }
+}
+
+impl BitAnd for Logical {
+    type Output = Logical;
+
+    fn bitand(self, rhs: Logical) -> Logical {
+        match self {
+            Logical::And(mut exprs) => {
+                exprs.push(rhs);
+
+                Logical::And(exprs)
+            }
+            lhs => Logical::And(vec![lhs, rhs]),
+        }
+    }
+}
+
+impl BitOr for Logical {
+    type Output = Logical;
+
+    fn bitor(self, rhs: Logical) -> Logical {
+        match self {
+            Logical::Or(mut exprs) => {
+                exprs.push(rhs);
+
+                Logical::Or(exprs)
+            }
+            lhs => Logical::Or(vec![lhs, rhs]),
+        }
+    }
+}
+
+impl Not for Logical {
+    type Output = Logical;
+
+    fn not(self) -> Logical {
+        Logical::Not(Box::new(self))
+    }
+}
+
+/// A logical combination of patterns with its own id.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Combination {
+    /// ID number of the combination, which is reported when the expression is true.
+    pub id: usize,
+    /// The logical expression over the ids of the sub-patterns.
+    pub expression: Logical,
+    /// Flags which modify the behaviour of the combination, `HS_FLAG_COMBINATION` is always set.
+    pub flags: CompileFlags,
+}
+
+impl Combination {
+    /// Constructs a combination of the logical expression.
+    pub fn new<L: Into<Logical>>(id: usize, expression: L) -> Combination {
+        Combination {
+            id,
+            expression: expression.into(),
+            flags: CompileFlags::default(),
+        }
+    }
+
+    /// Flags which modify the behaviour of the combination, such as `HS_FLAG_SINGLEMATCH`.
+    pub fn with_flags<F: Into<CompileFlags>>(mut self, flags: F) -> Self {
+        self.flags = flags.into();
+        self
+    }
+
+    /// The pattern of the combination in the Hyperscan logical syntax.
+    pub fn to_pattern(&self) -> Pattern {
+        let mut flags = self.flags;
+
+        flags.set(HS_FLAG_COMBINATION);
+
+        Pattern {
+            expression: self.expression.to_string(),
+            flags,
+            id: self.id,
+            ..Default::default()
+        }
+    }
+}
+
+/// A set of patterns with the logical combinations of them.
+///
+/// The ids referenced by the combinations are validated when the patterns are built,
+/// so a combination can't silently refer to a missing pattern.
+#[derive(Debug, Clone, Default)]
+pub struct Combinations {
+    patterns: Patterns,
+    combinations: Vec<Combination>,
+    quiet: bool,
+}
+
+impl Combinations {
+    /// Constructs a set of the sub-patterns which the combinations refer to.
+    pub fn new(patterns: Patterns) -> Combinations {
+        Combinations {
+            patterns,
+            ..Default::default()
+        }
+    }
+
+    /// Add a combination.
+    pub fn with(mut self, combination: Combination) -> Self {
+        self.combinations.push(combination);
+        self
+    }
+
+    /// Mark the sub-patterns which are referenced by any combination with `HS_FLAG_QUIET`,
+    /// so only the combinations are reported.
+    pub fn with_quiet(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        self
+    }
+
+    /// The combinations.
+    pub fn combinations(&self) -> &[Combination] {
+        &self.combinations
+    }
+
+    /// Validate the combinations, and render the sub-patterns and combinations to the patterns to compile.
+    ///
+    /// Each combination must have a unique id, and only refer to the ids of the sub-patterns.
+    pub fn patterns(&self) -> Result<Patterns, Error> {
+        let ids = self
+            .patterns
+            .iter()
+            .filter(|pattern| !pattern.flags.is_set(HS_FLAG_COMBINATION))
+            .map(|pattern| pattern.id)
+            .collect::<HashSet<_>>();
+        let mut used = self.patterns.iter().map(|pattern| pattern.id).collect::<HashSet<_>>();
+        let mut referenced = HashSet::new();
+
+        for (i, combination) in self.combinations.iter().enumerate() {
+            let err = |message: String| {
+                Err(Error::ExpressionError(ExpressionError {
+                    message,
+                    index: self.patterns.len() + i,
+                    id: Some(combination.id),
+                    expression: Some(combination.expression.to_string()),
+                }))
+            };
+
+            if combination.expression.has_empty_operands() {
+                return err("empty logical operands".to_owned());
+            }
+            if !used.insert(combination.id) {
+                return err(format!("duplicate id {}", combination.id));
+            }
+
+            for id in combination.expression.ids() {
+                if !ids.contains(&id) {
+                    return err(format!("unknown sub-expression id {}", id));
+                }
+
+                referenced.insert(id);
+            }
+        }
+
+        let mut patterns = self.patterns.clone();
+
+        if self.quiet {
+            for pattern in &mut patterns {
+                if referenced.contains(&pattern.id) {
+                    pattern.flags.set(HS_FLAG_QUIET);
+                }
+            }
+        }
+
+        patterns.extend(self.combinations.iter().map(Combination::to_pattern));
+
+        Ok(patterns)
+    }
+}
+
+impl<T: Type> DatabaseBuilder<RawDatabase<T>> for Combinations {
+    /// Validate the combinations, and compile them with the sub-patterns.
+    fn build_for_platform(&self, platform: &PlatformInfo) -> Result<RawDatabase<T>, Error> {
+        try!(self.patterns()).build_for_platform(platform)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    extern crate env_logger;
+
+    use super::super::*;
+
+    #[test]
+    fn test_logical() {
+        let _ = env_logger::try_init();
+
+        let expr = (Logical::id(101) & Logical::id(102)) | !Logical::id(103);
+
+        assert_eq!(expr.to_string(), "(101 & 102) | !103");
+        assert_eq!(expr.ids(), vec![101, 102, 103]);
+
+        let expr = Logical::id(1) & Logical::id(2) & !(Logical::id(3) | Logical::id(1));
+
+        assert_eq!(expr.to_string(), "1 & 2 & !(3 | 1)");
+        assert_eq!(expr.ids(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_combinations() {
+        let _ = env_logger::try_init();
+
+        let patterns = patterns!(["foo", "bar", "baz"]);
+        let combinations = Combinations::new(patterns.clone())
+            .with(Combination::new(10, Logical::id(1) & Logical::id(2)))
+            .with(Combination::new(11, Logical::id(1) & !Logical::id(3)).with_flags(HS_FLAG_SINGLEMATCH))
+            .with_quiet(true);
+
+        let rendered = combinations.patterns().unwrap();
+
+        assert_eq!(
+            rendered.iter().map(|p| p.to_string()).collect::<Vec<_>>(),
+            vec!["1:/foo/Q", "2:/bar/Q", "3:/baz/Q", "10:/1 & 2/C", "11:/1 & !3/HC"]
+        );
+
+        let db: BlockDatabase = combinations.build().unwrap();
+        let s = db.alloc().unwrap();
+        let mut matches = vec![];
+
+        db.scan("foo bar", 0, &s, |m: Match| {
+            matches.push(m.id);
+
+            Matching::Continue
+        })
+        .unwrap();
+
+        matches.sort();
+        matches.dedup();
+
+        assert_eq!(matches, vec![10, 11]);
+    }
+
+    #[test]
+    fn test_combinations_error() {
+        let _ = env_logger::try_init();
+
+        let patterns = patterns!(["foo", "bar"]);
+
+        let res: Result<BlockDatabase, Error> = Combinations::new(patterns.clone())
+            .with(Combination::new(10, Logical::id(1) | Logical::id(3)))
+            .build();
+
+        match res {
+            Err(Error::ExpressionError(err)) => {
+                assert_eq!(err.message, "unknown sub-expression id 3");
+                assert_eq!(err.index, 2);
+                assert_eq!(err.id, Some(10));
+                assert_eq!(err.expression, Some("1 | 3".to_owned()));
+            }
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        assert!(Combinations::new(patterns.clone())
+            .with(Combination::new(2, Logical::id(1)))
+            .patterns()
+            .is_err());
+        assert!(Combinations::new(patterns)
+            .with(Combination::new(10, Logical::Or(vec![])))
+            .patterns()
+            .is_err());
+    }
+}
@@ -0,0 +1,204 @@
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Mutex;
+
+use api::*;
+use errors::Error;
+use runtime::RawScratch;
+
+/// A pool of scratch spaces for a database, which could be shared by threads.
+///
+/// Each concurrent caller takes a scratch space from the pool,
+/// which is cloned from the prototype when the pool is empty,
+/// and returned to the pool when the guard is dropped.
+pub struct ScratchPool {
+    prototype: RawScratch,
+    free: Mutex<Vec<RawScratch>>,
+}
+
+impl fmt::Debug for ScratchPool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ScratchPool{{prototype: {:p}, free: {}}}",
+            *self.prototype,
+            self.len()
+        )
+    }
+}
+
+// The prototype is only read when cloning, and never used for scanning.
+unsafe impl Sync for ScratchPool {}
+
+impl ScratchPool {
+    /// Constructs an empty pool of scratch spaces for the database.
+    pub fn new<D: Database>(db: &D) -> Result<ScratchPool, Error> {
+        Ok(ScratchPool {
+            prototype: try!(RawScratch::alloc(db)),
+            free: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Constructs a pool of scratch spaces for the database with some preallocated scratch spaces.
+    pub fn with_capacity<D: Database>(db: &D, capacity: usize) -> Result<ScratchPool, Error> {
+        let pool = try!(ScratchPool::new(db));
+
+        {
+            let mut free = pool.free.lock().unwrap();
+
+            for _ in 0..capacity {
+                free.push(try!(pool.prototype.try_clone()));
+            }
+        }
+
+        Ok(pool)
+    }
+
+    /// The number of free scratch spaces in the pool.
+    pub fn len(&self) -> usize {
+        self.free.lock().unwrap().len()
+    }
+
+    /// Whether there is no free scratch space in the pool.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Take a scratch space from the pool, or clone a new one from the prototype if the pool is empty.
+    pub fn get(&self) -> Result<PooledScratch<'_>, Error> {
+        let scratch = self.free.lock().unwrap().pop();
+
+        let scratch = match scratch {
+            Some(scratch) => scratch,
+            None => try!(self.prototype.try_clone()),
+        };
+
+        Ok(PooledScratch {
+            pool: self,
+            scratch: Some(scratch),
+        })
+    }
+}
+
+/// A scratch space taken from `ScratchPool`, which is returned to the pool when dropped.
+pub struct PooledScratch<'a> {
+    pool: &'a ScratchPool,
+    scratch: Option<RawScratch>,
+}
+
+impl<'a> fmt::Debug for PooledScratch<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PooledScratch({:p})", **self)
+    }
+}
+
+impl<'a> PooledScratch<'a> {
+    #[inline]
+    fn scratch(&self) -> &RawScratch {
+        self.scratch.as_ref().unwrap()
+    }
+}
+
+impl<'a> Deref for PooledScratch<'a> {
+    type Target = RawScratchPtr;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.scratch()
+    }
+}
+
+impl<'a> Scratch for PooledScratch<'a> {
+    #[inline]
+    fn size(&self) -> Result<usize, Error> {
+        self.scratch().size()
+    }
+
+    #[inline]
+    fn realloc<T: Database>(&mut self, db: &T) -> Result<&Self, Error> {
+        try!(self.scratch.as_mut().unwrap().realloc(db));
+
+        Ok(self)
+    }
+}
+
+impl<'a> Drop for PooledScratch<'a> {
+    #[inline]
+    fn drop(&mut self) {
+        if let Some(scratch) = self.scratch.take() {
+            if let Ok(mut free) = self.pool.free.lock() {
+                free.push(scratch);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    extern crate env_logger;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::super::*;
+
+    #[test]
+    fn test_scratch_pool() {
+        let _ = env_logger::try_init();
+
+        let db: BlockDatabase = pattern! {"test"}.build().unwrap();
+        let pool = ScratchPool::with_capacity(&db, 1).unwrap();
+
+        assert_eq!(pool.len(), 1);
+
+        {
+            let s1 = pool.get().unwrap();
+            let s2 = pool.get().unwrap();
+
+            assert!(pool.is_empty());
+            assert!(*s1 != *s2);
+
+            db.scan("test", 0, &s1, |_| Matching::Continue).unwrap();
+        }
+
+        assert_eq!(pool.len(), 2);
+    }
+
+    #[test]
+    fn test_scratch_pool_threads() {
+        let _ = env_logger::try_init();
+
+        let db: Arc<BlockDatabase> = Arc::new(pattern! {"test"}.build().unwrap());
+        let pool = Arc::new(ScratchPool::new(&*db).unwrap());
+        let matches = Arc::new(AtomicUsize::new(0));
+
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let db = db.clone();
+                let pool = pool.clone();
+                let matches = matches.clone();
+
+                thread::spawn(move || {
+                    for _ in 0..10 {
+                        let s = pool.get().unwrap();
+
+                        db.scan("foo test bar", 0, &s, |_| {
+                            matches.fetch_add(1, Ordering::Relaxed);
+
+                            Matching::Continue
+                        })
+                        .unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        for t in threads {
+            t.join().unwrap();
+        }
+
+        assert_eq!(matches.load(Ordering::Relaxed), 40);
+        assert!(pool.len() <= 4);
+    }
+}
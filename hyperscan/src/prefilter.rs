@@ -0,0 +1,524 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+#[cfg(feature = "fancy-regex")]
+use std::str;
+
+#[cfg(feature = "fancy-regex")]
+use fancy_regex;
+#[cfg(feature = "regex")]
+use regex::bytes::{Regex, RegexBuilder};
+
+use api::*;
+use common::BlockDatabase;
+use compile::{Pattern, Patterns};
+use constants::*;
+use errors::Error;
+#[cfg(any(feature = "regex", feature = "fancy-regex"))]
+use errors::ExpressionError;
+use runtime::RawScratch;
+
+/// Confirm the candidate matches of the patterns compiled with `HS_FLAG_PREFILTER`.
+///
+/// In prefiltering mode, Hyperscan compiles an approximation of the pattern which may report false positives,
+/// such as for the backreferences and lookarounds it doesn't support, so each match must be confirmed.
+pub trait Verifier {
+    /// Whether the pattern has a match ending at the offset `to` of the data.
+    fn verify(&self, pattern: &Pattern, data: &[u8], to: usize) -> bool;
+}
+
+impl<F> Verifier for F
+where
+    F: Fn(&Pattern, &[u8], usize) -> bool,
+{
+    fn verify(&self, pattern: &Pattern, data: &[u8], to: usize) -> bool {
+        self(pattern, data, to)
+    }
+}
+
+#[cfg(any(feature = "regex", feature = "fancy-regex"))]
+fn expression_error(message: String, index: usize, pattern: &Pattern) -> Error {
+    Error::ExpressionError(ExpressionError {
+        message,
+        index,
+        id: Some(pattern.id),
+        expression: Some(pattern.expression.clone()),
+    })
+}
+
+/// The maximum width of the matches of a prefiltered pattern, if it is bounded.
+#[cfg(any(feature = "regex", feature = "fancy-regex"))]
+fn max_width(pattern: &Pattern) -> Option<usize> {
+    // the approximation of a prefiltered pattern matches at least what the pattern does,
+    // so its maximum width still bounds the matches of the pattern
+    pattern
+        .info()
+        .ok()
+        .map(|info| info.max_width)
+        .filter(|&width| width != u32::MAX as usize)
+}
+
+/// A verifier which confirms the matches with the `regex` crate.
+///
+/// It can't confirm the patterns with backreferences or lookarounds, which the `regex` crate doesn't support,
+/// so `RegexVerifier::new` fails with `Error::ExpressionError` for them.
+/// Use `FancyRegexVerifier` to confirm those patterns.
+#[cfg(feature = "regex")]
+#[derive(Debug, Clone)]
+pub struct RegexVerifier {
+    regexes: HashMap<usize, Vec<(Regex, Option<usize>)>>,
+}
+
+#[cfg(feature = "regex")]
+impl RegexVerifier {
+    /// Compile the prefiltered patterns with the `regex` crate.
+    ///
+    /// The regular expressions are anchored at the end, and only the data within the maximum width
+    /// of the pattern before the end of the match is searched, if the width is bounded.
+    pub fn new(patterns: &[Pattern]) -> Result<RegexVerifier, Error> {
+        let mut regexes = HashMap::new();
+
+        for (index, pattern) in patterns.iter().enumerate() {
+            if !pattern.flags.is_set(HS_FLAG_PREFILTER) {
+                continue;
+            }
+
+            let flags = pattern.flags;
+            let regex = try!(RegexBuilder::new(&format!("(?:{})\\z", pattern.expression))
+                .case_insensitive(flags.is_set(HS_FLAG_CASELESS))
+                .multi_line(flags.is_set(HS_FLAG_MULTILINE))
+                .dot_matches_new_line(flags.is_set(HS_FLAG_DOTALL))
+                .unicode(flags.is_set(HS_FLAG_UTF8) || flags.is_set(HS_FLAG_UCP))
+                .build()
+                .map_err(|err| expression_error(err.to_string(), index, pattern)));
+
+            regexes
+                .entry(pattern.id)
+                .or_insert_with(Vec::new)
+                .push((regex, max_width(pattern)));
+        }
+
+        Ok(RegexVerifier { regexes })
+    }
+}
+
+#[cfg(feature = "regex")]
+impl Verifier for RegexVerifier {
+    fn verify(&self, pattern: &Pattern, data: &[u8], to: usize) -> bool {
+        let data = &data[..to];
+
+        self.regexes
+            .get(&pattern.id)
+            .map(|regexes| {
+                regexes.iter().any(|&(ref regex, max_width)| {
+                    // search from the earliest start of the match, the anchors still see the data before it
+                    let start = max_width.map_or(0, |width| to.saturating_sub(width));
+
+                    regex.is_match_at(data, start)
+                })
+            })
+            .unwrap_or(false)
+    }
+}
+
+/// A verifier which confirms the matches with the backtracking `fancy-regex` crate.
+///
+/// It confirms the patterns with backreferences and lookarounds, which Hyperscan only supports in prefiltering mode.
+/// The data is decoded as UTF-8 for the patterns compiled with `HS_FLAG_UTF8`, and as Latin-1 for the others.
+///
+/// A candidate is confirmed if the engine gives up on it, such as when the backtrack limit is exceeded,
+/// so a pathological pattern can't hide its matches.
+#[cfg(feature = "fancy-regex")]
+#[derive(Debug, Clone)]
+pub struct FancyRegexVerifier {
+    regexes: HashMap<usize, Vec<(fancy_regex::Regex, Option<usize>)>>,
+}
+
+#[cfg(feature = "fancy-regex")]
+impl FancyRegexVerifier {
+    /// Compile the prefiltered patterns with the `fancy-regex` crate.
+    ///
+    /// The regular expressions are anchored at the end, and only the data within the maximum width
+    /// of the pattern before the end of the match is searched, if the width is bounded and it has no lookbehind.
+    pub fn new(patterns: &[Pattern]) -> Result<FancyRegexVerifier, Error> {
+        let mut regexes = HashMap::new();
+
+        for (index, pattern) in patterns.iter().enumerate() {
+            if !pattern.flags.is_set(HS_FLAG_PREFILTER) {
+                continue;
+            }
+
+            let flags = pattern.flags;
+            let mut expr = String::new();
+
+            if flags.is_set(HS_FLAG_CASELESS) {
+                expr.push_str("(?i)");
+            }
+            if flags.is_set(HS_FLAG_MULTILINE) {
+                expr.push_str("(?m)");
+            }
+            if flags.is_set(HS_FLAG_DOTALL) {
+                expr.push_str("(?s)");
+            }
+            expr.push_str(&format!("(?:{})\\z", pattern.expression));
+
+            let regex =
+                try!(fancy_regex::Regex::new(&expr).map_err(|err| expression_error(err.to_string(), index, pattern)));
+
+            // a lookbehind may look at the data before the earliest start of the match
+            let max_width = if pattern.expression.contains("(?<=") || pattern.expression.contains("(?<!") {
+                None
+            } else {
+                max_width(pattern)
+            };
+
+            regexes
+                .entry(pattern.id)
+                .or_insert_with(Vec::new)
+                .push((regex, max_width));
+        }
+
+        Ok(FancyRegexVerifier { regexes })
+    }
+}
+
+#[cfg(feature = "fancy-regex")]
+impl Verifier for FancyRegexVerifier {
+    fn verify(&self, pattern: &Pattern, data: &[u8], to: usize) -> bool {
+        let utf8 = pattern.flags.is_set(HS_FLAG_UTF8);
+
+        self.regexes
+            .get(&pattern.id)
+            .map(|regexes| {
+                regexes.iter().any(|&(ref regex, max_width)| {
+                    // keep one character before the earliest start of the match, so the anchors still see it
+                    let mut start = max_width.map_or(0, |width| to.saturating_sub(width));
+                    let mut from = start.saturating_sub(1);
+
+                    if utf8 {
+                        while start > 0 && data[start] & 0xC0 == 0x80 {
+                            start -= 1;
+                        }
+                        from = start.saturating_sub(1);
+                        while from > 0 && data[from] & 0xC0 == 0x80 {
+                            from -= 1;
+                        }
+                    }
+
+                    let window = &data[from..to];
+                    let (text, pos) = if utf8 {
+                        match str::from_utf8(window) {
+                            Ok(text) => (text.to_owned(), start - from),
+                            Err(_) => return false,
+                        }
+                    } else {
+                        let head = window[..start - from].iter().map(|&b| b as char).collect::<String>();
+                        let pos = head.len();
+
+                        (
+                            head + &window[start - from..].iter().map(|&b| b as char).collect::<String>(),
+                            pos,
+                        )
+                    };
+
+                    match regex.find_from_pos(&text, pos) {
+                        Ok(m) => m.is_some(),
+                        Err(err) => {
+                            warn!("fail to confirm pattern #{} at {}, {}", pattern.id, to, err);
+
+                            true
+                        }
+                    }
+                })
+            })
+            .unwrap_or(false)
+    }
+}
+
+/// A block database which confirms the matches of the prefiltered patterns before reporting them.
+///
+/// The patterns compiled with `HS_FLAG_PREFILTER` are confirmed by the verifier,
+/// and only the confirmed matches reach the callback. The other patterns are reported as usual.
+///
+/// A match can only be attributed to its pattern by id, so a prefiltered pattern should have an unique id,
+/// the matches of an id shared with a pattern which isn't prefiltered are not confirmed.
+///
+/// `HS_FLAG_SINGLEMATCH` is cleared from the prefiltered patterns when they are compiled,
+/// so a false positive can't hide the later matches, and only the first confirmed match of them is reported.
+pub struct PrefilterDatabase<V> {
+    db: BlockDatabase,
+    prefiltered: HashMap<u32, Vec<Pattern>>,
+    single_match: HashSet<u32>,
+    verifier: V,
+}
+
+impl<V> fmt::Debug for PrefilterDatabase<V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "PrefilterDatabase{{db: {:p}, prefiltered: {}}}",
+            *self.db,
+            self.prefiltered.len()
+        )
+    }
+}
+
+#[cfg(feature = "regex")]
+impl PrefilterDatabase<RegexVerifier> {
+    /// Compile the patterns, and confirm the prefiltered patterns with the `regex` crate.
+    ///
+    /// It fails with `Error::ExpressionError` if a prefiltered pattern has backreferences or lookarounds,
+    /// use `PrefilterDatabase::with_fancy_regex` for them.
+    pub fn with_regex(patterns: &Patterns) -> Result<Self, Error> {
+        let verifier = try!(RegexVerifier::new(patterns));
+
+        Self::build(patterns, verifier)
+    }
+}
+
+#[cfg(feature = "fancy-regex")]
+impl PrefilterDatabase<FancyRegexVerifier> {
+    /// Compile the patterns, and confirm the prefiltered patterns with the `fancy-regex` crate.
+    pub fn with_fancy_regex(patterns: &Patterns) -> Result<Self, Error> {
+        let verifier = try!(FancyRegexVerifier::new(patterns));
+
+        Self::build(patterns, verifier)
+    }
+}
+
+impl<V: Verifier> PrefilterDatabase<V> {
+    /// Compile the patterns, and confirm the prefiltered patterns with the verifier.
+    pub fn build(patterns: &Patterns, verifier: V) -> Result<Self, Error> {
+        Self::build_for_platform(patterns, verifier, &PlatformInfo::null())
+    }
+
+    /// Compile the patterns for the platform, and confirm the prefiltered patterns with the verifier.
+    pub fn build_for_platform(patterns: &Patterns, verifier: V, platform: &PlatformInfo) -> Result<Self, Error> {
+        let mut prefiltered = HashMap::new();
+
+        for pattern in patterns {
+            if pattern.flags.is_set(HS_FLAG_PREFILTER) {
+                prefiltered
+                    .entry(pattern.id as u32)
+                    .or_insert_with(Vec::new)
+                    .push(pattern.clone());
+            }
+        }
+        for pattern in patterns {
+            if !pattern.flags.is_set(HS_FLAG_PREFILTER) {
+                prefiltered.remove(&(pattern.id as u32));
+            }
+        }
+
+        let mut single_match = HashSet::new();
+        let mut patterns = patterns.clone();
+
+        for pattern in &mut patterns {
+            if pattern.flags.is_set(HS_FLAG_SINGLEMATCH) && prefiltered.contains_key(&(pattern.id as u32)) {
+                pattern.flags.0 &= !HS_FLAG_SINGLEMATCH;
+                single_match.insert(pattern.id as u32);
+            }
+        }
+
+        let db = try!(patterns.build_for_platform(platform));
+
+        debug!(
+            "compiled {} patterns with {} prefiltered to block database {:p}",
+            patterns.len(),
+            prefiltered.len(),
+            *db
+        );
+
+        Ok(PrefilterDatabase {
+            db,
+            prefiltered,
+            single_match,
+            verifier,
+        })
+    }
+
+    /// The underlying block database.
+    pub fn database(&self) -> &BlockDatabase {
+        &self.db
+    }
+
+    /// The verifier of the prefiltered patterns.
+    pub fn verifier(&self) -> &V {
+        &self.verifier
+    }
+
+    /// Allocate a scratch space for the database.
+    pub fn alloc(&self) -> Result<RawScratch, Error> {
+        RawScratch::alloc(&self.db)
+    }
+
+    /// Scan the data, and report the matches of the prefiltered patterns only if they are confirmed.
+    pub fn scan<D, F>(&self, data: D, flags: ScanFlags, scratch: &RawScratch, mut on_match: F) -> Result<&Self, Error>
+    where
+        D: Scannable,
+        F: MatchEventHandler,
+    {
+        let bytes = data.as_bytes();
+        let mut reported = HashSet::new();
+
+        try!(self.db.scan(bytes, flags, scratch, |m: Match| {
+            if let Some(patterns) = self.prefiltered.get(&m.id) {
+                let to = m.to as usize;
+
+                if self.single_match.contains(&m.id) && reported.contains(&m.id) {
+                    return Matching::Continue;
+                }
+                if !patterns.iter().any(|pattern| self.verifier.verify(pattern, bytes, to)) {
+                    trace!("prefiltered match of pattern #{} at {} is not confirmed", m.id, to);
+
+                    return Matching::Continue;
+                }
+
+                reported.insert(m.id);
+            }
+
+            on_match(m)
+        }));
+
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    extern crate env_logger;
+
+    #[cfg(any(feature = "regex", feature = "fancy-regex"))]
+    use std::slice;
+
+    use super::super::*;
+
+    fn scan<V: Verifier>(db: &PrefilterDatabase<V>, data: &str) -> Vec<(u32, u64)> {
+        let s = db.alloc().unwrap();
+        let mut matches = vec![];
+
+        db.scan(data, 0, &s, |m: Match| {
+            matches.push((m.id, m.to));
+
+            Matching::Continue
+        })
+        .unwrap();
+
+        matches
+    }
+
+    #[test]
+    fn test_prefilter_database() {
+        let _ = env_logger::try_init();
+
+        let patterns = vec![
+            pattern! {"(a+)b\\1", flags => HS_FLAG_PREFILTER, id => 1},
+            pattern! {"foo", flags => 0, id => 2},
+        ];
+
+        let db = PrefilterDatabase::build(&patterns, |_: &Pattern, _: &[u8], _: usize| false).unwrap();
+
+        assert_eq!(scan(&db, "aaba foo"), vec![(2, 8)]);
+
+        // confirm `(a+)b\1` by hand, the match must end with `b` followed by as many `a` as before it
+        let db = PrefilterDatabase::build(&patterns, |pattern: &Pattern, data: &[u8], to: usize| {
+            assert_eq!(pattern.id, 1);
+
+            let data = &data[..to];
+
+            (1..data.len() / 2 + 1).any(|n| {
+                data.len() > 2 * n
+                    && data[data.len() - n..].iter().all(|&b| b == b'a')
+                    && data[data.len() - n - 1] == b'b'
+                    && data[data.len() - 2 * n - 1..data.len() - n - 1]
+                        .iter()
+                        .all(|&b| b == b'a')
+            })
+        })
+        .unwrap();
+
+        // `abaa` only matches `aba`, the candidate ending at the last `a` is dropped
+        let matches = scan(&db, "abaa foo");
+
+        assert!(!matches.contains(&(1, 4)));
+        assert!(matches.contains(&(2, 8)));
+    }
+
+    #[test]
+    fn test_prefilter_single_match() {
+        let _ = env_logger::try_init();
+
+        let patterns = vec![pattern! {"a+b", flags => HS_FLAG_PREFILTER | HS_FLAG_SINGLEMATCH, id => 1}];
+
+        // the first candidate is a false positive, which must not hide the later matches
+        let db = PrefilterDatabase::build(&patterns, |_: &Pattern, _: &[u8], to: usize| to > 2).unwrap();
+
+        assert_eq!(scan(&db, "ab ab ab"), vec![(1, 5)]);
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_regex_verifier() {
+        let _ = env_logger::try_init();
+
+        let patterns = vec![
+            pattern! {"fo+bar", flags => HS_FLAG_PREFILTER | HS_FLAG_CASELESS, id => 1},
+            pattern! {"test", flags => 0, id => 2},
+        ];
+
+        let db = PrefilterDatabase::with_regex(&patterns).unwrap();
+
+        assert_eq!(scan(&db, "FOOBAR test"), vec![(1, 6), (2, 11)]);
+        assert_eq!(scan(&db, "fbar"), vec![]);
+
+        // the search is bounded by the width of the pattern, but the anchors still see the whole data
+        let pattern = pattern! {"^foo", flags => HS_FLAG_PREFILTER, id => 1};
+        let verifier = RegexVerifier::new(slice::from_ref(&pattern)).unwrap();
+
+        assert!(verifier.verify(&pattern, b"foo foo", 3));
+        assert!(!verifier.verify(&pattern, b"foo foo", 7));
+
+        let pattern = pattern! {"\\bbar", flags => HS_FLAG_PREFILTER, id => 1};
+        let verifier = RegexVerifier::new(slice::from_ref(&pattern)).unwrap();
+
+        assert!(verifier.verify(&pattern, b"foo bar", 7));
+        assert!(!verifier.verify(&pattern, b"foobar", 6));
+
+        match PrefilterDatabase::with_regex(&vec![pattern! {"(a+)b\\1", flags => HS_FLAG_PREFILTER, id => 1}]) {
+            Err(Error::ExpressionError(err)) => assert_eq!(err.id, Some(1)),
+            res => panic!("unexpected result: {:?}", res),
+        }
+    }
+
+    #[cfg(feature = "fancy-regex")]
+    #[test]
+    fn test_fancy_regex_verifier() {
+        let _ = env_logger::try_init();
+
+        let patterns = vec![
+            pattern! {"(a+)b\\1", flags => HS_FLAG_PREFILTER, id => 1},
+            pattern! {"foo", flags => 0, id => 2},
+        ];
+
+        let db = PrefilterDatabase::with_fancy_regex(&patterns).unwrap();
+
+        // `abaa` only matches `aba`, the candidate ending at the last `a` is dropped
+        let matches = scan(&db, "abaa foo");
+
+        assert!(matches.contains(&(1, 3)));
+        assert!(!matches.contains(&(1, 4)));
+        assert!(matches.contains(&(2, 8)));
+
+        let pattern = pattern! {"(?<=foo)bar", flags => HS_FLAG_PREFILTER | HS_FLAG_CASELESS, id => 1};
+        let verifier = FancyRegexVerifier::new(slice::from_ref(&pattern)).unwrap();
+
+        assert!(verifier.verify(&pattern, b"FOOBAR", 6));
+        assert!(!verifier.verify(&pattern, b"fozbar", 6));
+
+        let pattern = pattern! {"\\bbar", flags => HS_FLAG_PREFILTER, id => 1};
+        let verifier = FancyRegexVerifier::new(slice::from_ref(&pattern)).unwrap();
+
+        assert!(verifier.verify(&pattern, b"foo bar", 7));
+        assert!(!verifier.verify(&pattern, b"foobar", 6));
+    }
+}
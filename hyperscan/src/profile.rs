@@ -0,0 +1,204 @@
+use std::cmp::Reverse;
+use std::time::{Duration, Instant};
+
+use api::*;
+use common::RawDatabase;
+use compile::{Pattern, Patterns};
+use constants::*;
+use errors::Error;
+use raw::*;
+use runtime::RawScratch;
+
+/// The compile cost of a pattern.
+#[derive(Debug, Clone)]
+pub struct PatternCost {
+    /// The profiled pattern.
+    pub pattern: Pattern,
+    /// The information of the pattern.
+    pub info: ExpressionInfo,
+    /// The time to compile the database.
+    pub compile_time: Duration,
+    /// The size of the database in bytes.
+    pub database_size: usize,
+    /// The size of the stream state in bytes, only for the streaming mode.
+    pub stream_size: Option<usize>,
+    /// The size of the scratch space in bytes.
+    pub scratch_size: usize,
+}
+
+impl PatternCost {
+    /// The total memory cost in bytes, which the patterns are ranked by.
+    pub fn cost(&self) -> usize {
+        self.database_size + self.stream_size.unwrap_or(0) + self.scratch_size
+    }
+}
+
+/// The result of profiling the patterns.
+#[derive(Debug, Clone)]
+pub struct ProfileReport {
+    /// The cost of the patterns which could be profiled.
+    pub costs: Vec<PatternCost>,
+    /// The patterns which fail to be analyzed or compiled, with their errors.
+    pub errors: Vec<(Pattern, Error)>,
+}
+
+/// Profile the compile cost of patterns.
+pub trait Profile {
+    /// Compile each pattern alone in the mode of `T`, and rank them by cost, the most expensive first.
+    ///
+    /// A pattern which fails to compile is recorded with its error, and the other patterns are still profiled.
+    fn profile<T: Type>(&self, platform: &PlatformInfo) -> ProfileReport;
+
+    /// Compile the patterns cumulatively in the mode of `T`, in the order of the patterns.
+    ///
+    /// The cost of each pattern is the cost of the database compiled from the patterns
+    /// up to and including it, so the growth of the database could be tracked.
+    /// A pattern which fails to compile is recorded with its error, and left out of the following databases.
+    fn profile_cumulative<T: Type>(&self, platform: &PlatformInfo) -> ProfileReport;
+}
+
+impl Profile for Patterns {
+    fn profile<T: Type>(&self, platform: &PlatformInfo) -> ProfileReport {
+        let mut costs = Vec::with_capacity(self.len());
+        let mut errors = vec![];
+
+        for pattern in self {
+            match measure::<T, _>(pattern, || pattern.build_for_platform(platform)) {
+                Ok(cost) => costs.push(cost),
+                Err(err) => errors.push((pattern.clone(), err)),
+            }
+        }
+
+        costs.sort_by_key(|cost| Reverse(cost.cost()));
+
+        ProfileReport { costs, errors }
+    }
+
+    fn profile_cumulative<T: Type>(&self, platform: &PlatformInfo) -> ProfileReport {
+        let mut costs = Vec::with_capacity(self.len());
+        let mut errors = vec![];
+        let mut patterns = Vec::with_capacity(self.len());
+
+        for pattern in self {
+            patterns.push(pattern.clone());
+
+            match measure::<T, _>(pattern, || patterns.build_for_platform(platform)) {
+                Ok(cost) => costs.push(cost),
+                Err(err) => {
+                    patterns.pop();
+                    errors.push((pattern.clone(), err));
+                }
+            }
+        }
+
+        ProfileReport { costs, errors }
+    }
+}
+
+/// Compile the database of a pattern, and measure its cost.
+fn measure<T, F>(pattern: &Pattern, build: F) -> Result<PatternCost, Error>
+where
+    T: Type,
+    F: FnOnce() -> Result<RawDatabase<T>, Error>,
+{
+    let info = try!(pattern.info());
+
+    let start = Instant::now();
+    let db = try!(build());
+    let compile_time = start.elapsed();
+
+    let stream_size = if T::mode() == HS_MODE_STREAM {
+        let mut size = 0;
+
+        unsafe {
+            check_hs_error!(hs_stream_size(*db, &mut size));
+        }
+
+        Some(size)
+    } else {
+        None
+    };
+
+    let cost = PatternCost {
+        pattern: pattern.clone(),
+        info,
+        compile_time,
+        database_size: try!(db.database_size()),
+        stream_size,
+        scratch_size: try!(try!(RawScratch::alloc(&db)).size()),
+    };
+
+    trace!("pattern `{}` cost: {:?}", pattern, cost);
+
+    Ok(cost)
+}
+
+#[cfg(test)]
+pub mod tests {
+    extern crate env_logger;
+
+    use super::super::*;
+
+    #[test]
+    fn test_profile() {
+        let _ = env_logger::try_init();
+
+        let patterns = patterns!(["foo", "a[^b]{64}b", "bar"]);
+
+        let report = patterns.profile::<Streaming>(&PlatformInfo::null());
+
+        assert!(report.errors.is_empty());
+
+        let costs = report.costs;
+
+        assert_eq!(costs.len(), 3);
+        assert_eq!(costs[0].pattern.id, 2);
+        assert!(costs.windows(2).all(|w| w[0].cost() >= w[1].cost()));
+        assert!(costs.iter().all(|cost| cost.stream_size.is_some()));
+        assert!(costs.iter().all(|cost| cost.scratch_size > 0));
+
+        let costs = patterns.profile::<Block>(&PlatformInfo::null()).costs;
+
+        assert!(costs.iter().all(|cost| cost.stream_size.is_none()));
+    }
+
+    #[test]
+    fn test_profile_cumulative() {
+        let _ = env_logger::try_init();
+
+        let patterns = patterns!(["foo", "a[^b]{64}b", "bar"]);
+
+        let costs = patterns.profile_cumulative::<Block>(&PlatformInfo::null()).costs;
+
+        assert_eq!(
+            costs.iter().map(|cost| cost.pattern.id).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+
+        let db: BlockDatabase = patterns.build().unwrap();
+
+        assert_eq!(costs[2].database_size, db.database_size().unwrap());
+    }
+
+    #[test]
+    fn test_profile_error() {
+        let _ = env_logger::try_init();
+
+        let patterns = patterns!(["foo", "bar(", "baz"]);
+
+        let report = patterns.profile::<Block>(&PlatformInfo::null());
+
+        assert_eq!(report.costs.len(), 2);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].0.id, 2);
+
+        let report = patterns.profile_cumulative::<Block>(&PlatformInfo::null());
+
+        assert_eq!(
+            report.costs.iter().map(|cost| cost.pattern.id).collect::<Vec<_>>(),
+            vec![1, 3]
+        );
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].0.id, 2);
+    }
+}
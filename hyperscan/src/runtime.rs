@@ -1,12 +1,16 @@
+use std::any::Any;
+use std::cmp;
 use std::fmt;
 use std::mem;
 use std::ops::{Deref, DerefMut};
-use std::os::raw::c_uint;
+use std::os::raw::{c_char, c_int, c_uint, c_ulonglong, c_void};
+use std::panic::{self, AssertUnwindSafe};
 use std::ptr;
 
 use api::*;
 use common::{BlockDatabase, RawDatabase, StreamingDatabase, VectoredDatabase};
-use errors::Error;
+use constants::*;
+use errors::{Error, Operation};
 use raw::*;
 
 /// A large enough region of scratch space to support a given database.
@@ -25,11 +29,11 @@ impl RawScratch {
     /// This is required for runtime use, and one scratch space per thread,
     /// or concurrent caller, is required.
     ///
-    fn alloc<T: Database>(db: &T) -> Result<RawScratch, Error> {
+    pub fn alloc<T: Database>(db: &T) -> Result<RawScratch, Error> {
         let mut s: RawScratchPtr = ptr::null_mut();
 
         unsafe {
-            check_hs_error!(hs_alloc_scratch(**db, &mut s));
+            check_hs_error!(hs_alloc_scratch(**db, &mut s), Operation::AllocScratch);
         }
 
         trace!(
@@ -41,8 +45,24 @@ impl RawScratch {
 
         Ok(RawScratch(s))
     }
+
+    /// Allocate a scratch space that is a clone of an existing scratch space.
+    pub fn try_clone(&self) -> Result<RawScratch, Error> {
+        let mut s: RawScratchPtr = ptr::null_mut();
+
+        unsafe {
+            check_hs_error!(hs_clone_scratch(self.0, &mut s), Operation::CloneScratch);
+        }
+
+        trace!("cloned scratch from {:p} to {:p}", self.0, s);
+
+        Ok(RawScratch(s))
+    }
 }
 
+// The scratch space can be moved to another thread, but must not be used concurrently.
+unsafe impl Send for RawScratch {}
+
 impl Drop for RawScratch {
     #[inline]
     fn drop(&mut self) {
@@ -59,15 +79,7 @@ impl Drop for RawScratch {
 impl Clone for RawScratch {
     #[inline]
     fn clone(&self) -> Self {
-        let mut s: RawScratchPtr = ptr::null_mut();
-
-        unsafe {
-            assert_hs_error!(hs_clone_scratch(self.0, &mut s));
-        }
-
-        trace!("cloned scratch from {:p} to {:p}", self.0, s);
-
-        RawScratch(s)
+        self.try_clone().unwrap()
     }
 }
 
@@ -97,7 +109,7 @@ impl Scratch for RawScratch {
     #[inline]
     fn realloc<T: Database>(&mut self, db: &T) -> Result<&Self, Error> {
         unsafe {
-            check_hs_error!(hs_alloc_scratch(**db, &mut self.0));
+            check_hs_error!(hs_alloc_scratch(**db, &mut self.0), Operation::AllocScratch);
         }
 
         trace!(
@@ -125,91 +137,201 @@ impl<T: Type> ScratchAllocator<RawScratch> for RawDatabase<T> {
     }
 }
 
+/// The Rust callback and the panic caught from it, passed to Hyperscan as the callback context.
+///
+/// A panic must not unwind across the C frames of Hyperscan,
+/// so it is caught in the match event handler, which terminates the scan,
+/// and resumed after the scan function returned.
+struct MatchContext<F> {
+    on_match: F,
+    panic: Option<Box<dyn Any + Send + 'static>>,
+}
+
+impl<F> MatchContext<F>
+where
+    F: MatchEventHandler,
+{
+    #[inline]
+    fn new(on_match: F) -> Self {
+        MatchContext { on_match, panic: None }
+    }
+
+    /// The match event handler and the context which could be passed to Hyperscan.
+    #[inline]
+    fn as_raw(&mut self) -> (match_event_handler, *mut c_void) {
+        (Some(on_match_event::<F>), self as *mut Self as *mut c_void)
+    }
+
+    /// Resume the panic caught in the callback, if any.
+    #[inline]
+    fn resume_panic(&mut self) {
+        if let Some(err) = self.panic.take() {
+            panic::resume_unwind(err)
+        }
+    }
+}
+
+/// The `extern "C"` match event handler which forwards match events to the Rust callback in `context`.
+unsafe extern "C" fn on_match_event<F>(
+    id: c_uint,
+    from: c_ulonglong,
+    to: c_ulonglong,
+    flags: c_uint,
+    context: *mut c_void,
+) -> c_int
+where
+    F: MatchEventHandler,
+{
+    let ctx = &mut *(context as *mut MatchContext<F>);
+    let on_match = &mut ctx.on_match;
+
+    match panic::catch_unwind(AssertUnwindSafe(|| on_match(Match { id, from, to, flags }))) {
+        Ok(Matching::Continue) => 0,
+        Ok(Matching::Terminate) => 1,
+        Err(err) => {
+            ctx.panic = Some(err);
+
+            1
+        }
+    }
+}
+
+/// The maximum number of bytes which could be passed to Hyperscan in one call.
+const MAX_SCAN_LEN: usize = c_uint::max_value() as usize;
+
 impl<T: Scannable, S: Scratch> BlockScanner<T, S> for BlockDatabase {
+    /// The block scan can't be split, data larger than 4 GiB fails with `Error::DataTooLarge`.
     #[inline]
-    fn scan<D>(
-        &self,
-        data: T,
-        flags: ScanFlags,
-        scratch: &S,
-        callback: Option<MatchEventCallback<D>>,
-        context: Option<&D>,
-    ) -> Result<&Self, Error> {
-        unsafe {
-            let bytes = data.as_bytes();
+    fn scan<F>(&self, data: T, flags: ScanFlags, scratch: &S, on_match: F) -> Result<&Self, Error>
+    where
+        F: MatchEventHandler,
+    {
+        let bytes = data.as_bytes();
+
+        if bytes.len() > MAX_SCAN_LEN {
+            return Err(Error::DataTooLarge(bytes.len()).with_operation(Operation::Scan));
+        }
+
+        let mut ctx = MatchContext::new(on_match);
+        let (handler, context) = ctx.as_raw();
 
-            check_hs_error!(hs_scan(
+        let ret = unsafe {
+            hs_scan(
                 **self,
                 bytes.as_ptr() as *const i8,
                 bytes.len() as u32,
                 flags as u32,
                 **scratch,
-                mem::transmute(callback),
-                mem::transmute(context),
-            ));
-
-            trace!(
-                "block scan {} bytes with {} database at {:p}",
-                bytes.len(),
-                self.database_name(),
-                **self
+                handler,
+                context,
             )
-        }
+        };
+
+        ctx.resume_panic();
+
+        check_hs_error!(ret, Operation::Scan);
+
+        trace!(
+            "block scan {} bytes with {} database at {:p}",
+            bytes.len(),
+            self.database_name(),
+            **self
+        );
 
         Ok(&self)
     }
 }
 
 impl<T: Scannable, S: Scratch> VectoredScanner<T, S> for VectoredDatabase {
+    /// Segments larger than 4 GiB are split into multiple segments,
+    /// since the segments of a vectored scan are treated as contiguous data.
+    /// More than 4 Gi segments fail with `Error::TooManySegments`.
     #[inline]
-    fn scan<D>(
+    fn scan<F>(&self, data: &[T], flags: ScanFlags, scratch: &S, on_match: F) -> Result<&Self, Error>
+    where
+        F: MatchEventHandler,
+    {
+        try!(self.scan_segments(data, MAX_SCAN_LEN, flags, scratch, on_match));
+
+        Ok(&self)
+    }
+}
+
+impl VectoredDatabase {
+    fn scan_segments<T, S, F>(
         &self,
         data: &[T],
+        max_segment_len: usize,
         flags: ScanFlags,
         scratch: &S,
-        callback: Option<MatchEventCallback<D>>,
-        context: Option<&D>,
-    ) -> Result<&Self, Error> {
+        on_match: F,
+    ) -> Result<(), Error>
+    where
+        T: Scannable,
+        S: Scratch,
+        F: MatchEventHandler,
+    {
         let mut ptrs = Vec::with_capacity(data.len());
         let mut lens = Vec::with_capacity(data.len());
 
         for d in data.iter() {
             let bytes = d.as_bytes();
-            ptrs.push(bytes.as_ptr() as *const i8);
-            lens.push(bytes.len() as c_uint);
+
+            if bytes.is_empty() {
+                ptrs.push(bytes.as_ptr() as *const i8);
+                lens.push(0);
+            }
+
+            for segment in bytes.chunks(max_segment_len) {
+                ptrs.push(segment.as_ptr() as *const i8);
+                lens.push(segment.len() as c_uint);
+            }
         }
 
-        unsafe {
-            check_hs_error!(hs_scan_vector(
+        let total = lens.iter().map(|&len| u64::from(len)).sum::<u64>();
+
+        if ptrs.len() > MAX_SCAN_LEN {
+            return Err(Error::TooManySegments(ptrs.len()).with_operation(Operation::Scan));
+        }
+
+        let mut ctx = MatchContext::new(on_match);
+        let (handler, context) = ctx.as_raw();
+
+        let ret = unsafe {
+            hs_scan_vector(
                 **self,
                 ptrs.as_slice().as_ptr() as *const *const i8,
                 lens.as_slice().as_ptr() as *const c_uint,
-                data.len() as u32,
+                ptrs.len() as u32,
                 flags as u32,
                 **scratch,
-                mem::transmute(callback),
-                mem::transmute(context),
-            ));
-        }
+                handler,
+                context,
+            )
+        };
+
+        ctx.resume_panic();
+
+        check_hs_error!(ret, Operation::Scan);
 
         trace!(
             "vectored scan {} bytes in {} parts with {} database at {:p}",
-            lens.iter().sum::<u32>(),
+            total,
             lens.len(),
             self.database_name(),
             **self
         );
 
-        Ok(&self)
+        Ok(())
     }
 }
 
-impl StreamingScanner<RawStream, RawScratch> for StreamingDatabase {
-    fn open_stream(&self, flags: StreamFlags) -> Result<RawStream, Error> {
+impl<'a> StreamingScanner<'a, RawStream<'a>, RawScratch> for StreamingDatabase {
+    fn open_stream(&'a self, flags: StreamFlags) -> Result<RawStream<'a>, Error> {
         let mut id: RawStreamPtr = ptr::null_mut();
 
         unsafe {
-            check_hs_error!(hs_open_stream(**self, flags, &mut id));
+            check_hs_error!(hs_open_stream(**self, flags, &mut id), Operation::OpenStream);
         }
 
         trace!(
@@ -219,120 +341,283 @@ impl StreamingScanner<RawStream, RawScratch> for StreamingDatabase {
             **self
         );
 
-        Ok(RawStream(id))
+        Ok(RawStream { s: id, db: self })
     }
 }
 
 /// A pattern matching state can be maintained across multiple blocks of target data
-pub struct RawStream(RawStreamPtr);
+///
+/// The stream borrows the database it was opened for,
+/// and is closed without reporting any matches when it is dropped.
+pub struct RawStream<'a> {
+    s: RawStreamPtr,
+    db: &'a StreamingDatabase,
+}
 
-impl fmt::Debug for RawStream {
+impl<'a> fmt::Debug for RawStream<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "RawStream({:p})", self.0)
+        write!(f, "RawStream({:p})", self.s)
+    }
+}
+
+impl<'a> RawStream<'a> {
+    /// The database which the stream was opened for.
+    #[inline]
+    pub fn database(&self) -> &'a StreamingDatabase {
+        self.db
+    }
+
+    /// Creates a compressed representation of the stream state.
+    pub fn compress(&self) -> Result<Vec<u8>, Error> {
+        let mut buf = Vec::new();
+
+        try!(self.compress_into(&mut buf));
+
+        Ok(buf)
+    }
+
+    /// Creates a compressed representation of the stream state into the buffer,
+    /// reusing its allocation and growing it when the space is insufficient.
+    ///
+    /// Returns the size of the compressed representation, which is also the new length of the buffer.
+    pub fn compress_into(&self, buf: &mut Vec<u8>) -> Result<usize, Error> {
+        let capacity = buf.capacity();
+
+        buf.resize(capacity, 0);
+
+        loop {
+            let mut used: usize = 0;
+
+            let ret = unsafe { hs_compress_stream(self.s, buf.as_mut_ptr() as *mut c_char, buf.len(), &mut used) };
+
+            match ret {
+                HS_SUCCESS => {
+                    buf.truncate(used);
+
+                    trace!("stream at {:p} compressed to {} bytes", self.s, used);
+
+                    return Ok(used);
+                }
+                HS_INSUFFICIENT_SPACE if used > buf.len() => {
+                    trace!("stream at {:p} requires {} bytes to compress", self.s, used);
+
+                    buf.resize(used, 0);
+                }
+                _ => {
+                    buf.clear();
+
+                    return Err(Error::from(ret).with_operation(Operation::CompressStream));
+                }
+            }
+        }
+    }
+
+    /// Decompresses a compressed representation created by `RawStream::compress` into a new stream.
+    ///
+    /// # Safety
+    ///
+    /// `buf` must be a complete compressed representation of a stream that was opened against `db`.
+    /// Hyperscan is not always able to detect misuse, and the behaviour is undefined otherwise.
+    pub unsafe fn expand(db: &'a StreamingDatabase, buf: &[u8]) -> Result<RawStream<'a>, Error> {
+        let mut id: RawStreamPtr = ptr::null_mut();
+
+        check_hs_error!(
+            hs_expand_stream(**db, &mut id, buf.as_ptr() as *const c_char, buf.len()),
+            Operation::ExpandStream
+        );
+
+        trace!(
+            "stream expanded at {:p} from {} bytes for {} database at {:p}",
+            id,
+            buf.len(),
+            db.database_name(),
+            **db
+        );
+
+        Ok(RawStream { s: id, db })
+    }
+
+    /// Decompresses a compressed representation created by `RawStream::compress` on top of the stream.
+    ///
+    /// The stream will first be reset, reporting any matches at end of data (EOD).
+    ///
+    /// # Safety
+    ///
+    /// `buf` must be a complete compressed representation of a stream
+    /// that was opened against the same database as this stream.
+    /// Hyperscan is not always able to detect misuse, and the behaviour is undefined otherwise.
+    pub unsafe fn reset_and_expand<S, F>(&self, buf: &[u8], scratch: &S, on_match: F) -> Result<&Self, Error>
+    where
+        S: Scratch,
+        F: MatchEventHandler,
+    {
+        let mut ctx = MatchContext::new(on_match);
+        let (handler, context) = ctx.as_raw();
+
+        let ret = hs_reset_and_expand_stream(
+            self.s,
+            buf.as_ptr() as *const c_char,
+            buf.len(),
+            **scratch,
+            handler,
+            context,
+        );
+
+        ctx.resume_panic();
+
+        check_hs_error!(ret, Operation::ExpandStream);
+
+        trace!("stream reset and expanded at {:p} from {} bytes", self.s, buf.len());
+
+        Ok(self)
+    }
+}
+
+unsafe impl<'a> Send for RawStream<'a> {}
+
+impl<'a> Drop for RawStream<'a> {
+    #[inline]
+    fn drop(&mut self) {
+        if !self.s.is_null() {
+            unsafe {
+                assert_hs_error!(hs_close_stream(self.s, ptr::null_mut(), None, ptr::null_mut()));
+            }
+
+            trace!("stream dropped at {:p}", self.s);
+
+            self.s = ptr::null_mut();
+        }
     }
 }
 
-impl Deref for RawStream {
+impl<'a> Deref for RawStream<'a> {
     type Target = RawStreamPtr;
 
     #[inline]
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.s
     }
 }
 
-impl DerefMut for RawStream {
+impl<'a> DerefMut for RawStream<'a> {
     #[inline]
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+        &mut self.s
     }
 }
 
-impl Clone for RawStream {
+impl<'a> Clone for RawStream<'a> {
     fn clone(&self) -> Self {
         let mut id: RawStreamPtr = ptr::null_mut();
 
         unsafe {
-            assert_hs_error!(hs_copy_stream(&mut id, self.0));
+            assert_hs_error!(hs_copy_stream(&mut id, self.s));
         }
 
-        debug!("stream cloned from {:p} to {:p}", self.0, id);
+        debug!("stream cloned from {:p} to {:p}", self.s, id);
 
-        RawStream(id)
+        RawStream { s: id, db: self.db }
     }
 }
 
-impl<S: Scratch> Stream<S> for RawStream {
-    fn close<D>(
-        &self,
-        scratch: &S,
-        callback: Option<MatchEventCallback<D>>,
-        context: Option<&D>,
-    ) -> Result<&Self, Error> {
-        unsafe {
-            check_hs_error!(hs_close_stream(
-                self.0,
-                **scratch,
-                mem::transmute(callback),
-                mem::transmute(context),
-            ));
-        }
+impl<'a, S: Scratch> Stream<S> for RawStream<'a> {
+    fn close<F>(mut self, scratch: &S, on_match: F) -> Result<(), Error>
+    where
+        F: MatchEventHandler,
+    {
+        let s = mem::replace(&mut self.s, ptr::null_mut());
+        let mut ctx = MatchContext::new(on_match);
+        let (handler, context) = ctx.as_raw();
 
-        trace!("stream closed at {:p}", self.0);
+        let ret = unsafe { hs_close_stream(s, **scratch, handler, context) };
 
-        Ok(&self)
+        ctx.resume_panic();
+
+        check_hs_error!(ret, Operation::CloseStream);
+
+        trace!("stream closed at {:p}", s);
+
+        Ok(())
     }
 
-    fn reset<D>(
-        &self,
-        flags: StreamFlags,
-        scratch: &S,
-        callback: Option<MatchEventCallback<D>>,
-        context: Option<&D>,
-    ) -> Result<&Self, Error> {
-        unsafe {
-            check_hs_error!(hs_reset_stream(
-                self.0,
-                flags,
-                **scratch,
-                mem::transmute(callback),
-                mem::transmute(context),
-            ));
-        }
+    fn reset<F>(&self, flags: StreamFlags, scratch: &S, on_match: F) -> Result<&Self, Error>
+    where
+        F: MatchEventHandler,
+    {
+        let mut ctx = MatchContext::new(on_match);
+        let (handler, context) = ctx.as_raw();
+
+        let ret = unsafe { hs_reset_stream(self.s, flags, **scratch, handler, context) };
 
-        trace!("stream reset at {:p}", self.0);
+        ctx.resume_panic();
+
+        check_hs_error!(ret, Operation::ResetStream);
+
+        trace!("stream reset at {:p}", self.s);
 
         Ok(&self)
     }
 }
 
-impl<T: Scannable, S: Scratch> BlockScanner<T, S> for RawStream {
+impl<'a, T: Scannable, S: Scratch> BlockScanner<T, S> for RawStream<'a> {
+    /// Data larger than 4 GiB is scanned in chunks, the match offsets are still relative to the stream.
     #[inline]
-    fn scan<D>(
+    fn scan<F>(&self, data: T, flags: ScanFlags, scratch: &S, on_match: F) -> Result<&Self, Error>
+    where
+        F: MatchEventHandler,
+    {
+        try!(self.scan_chunks(data.as_bytes(), MAX_SCAN_LEN, flags, scratch, on_match));
+
+        Ok(&self)
+    }
+}
+
+impl<'a> RawStream<'a> {
+    fn scan_chunks<S, F>(
         &self,
-        data: T,
+        bytes: &[u8],
+        max_chunk_len: usize,
         flags: ScanFlags,
         scratch: &S,
-        callback: Option<MatchEventCallback<D>>,
-        context: Option<&D>,
-    ) -> Result<&Self, Error> {
-        let bytes = data.as_bytes();
-
-        unsafe {
-            check_hs_error!(hs_scan_stream(
-                self.0,
-                bytes.as_ptr() as *const i8,
-                bytes.len() as u32,
-                flags as u32,
-                **scratch,
-                mem::transmute(callback),
-                mem::transmute(context),
-            ));
+        on_match: F,
+    ) -> Result<(), Error>
+    where
+        S: Scratch,
+        F: MatchEventHandler,
+    {
+        let mut ctx = MatchContext::new(on_match);
+        let (handler, context) = ctx.as_raw();
+        let mut offset = 0;
+
+        loop {
+            let len = cmp::min(bytes.len() - offset, max_chunk_len);
+
+            let ret = unsafe {
+                hs_scan_stream(
+                    self.s,
+                    bytes[offset..].as_ptr() as *const i8,
+                    len as u32,
+                    flags as u32,
+                    **scratch,
+                    handler,
+                    context,
+                )
+            };
+
+            ctx.resume_panic();
+
+            check_hs_error!(ret, Operation::Scan);
+
+            offset += len;
+
+            if offset >= bytes.len() {
+                break;
+            }
         }
 
-        trace!("stream scan {} bytes with stream at {:p}", bytes.len(), self.0);
+        trace!("stream scan {} bytes with stream at {:p}", bytes.len(), self.s);
 
-        Ok(&self)
+        Ok(())
     }
 }
 
@@ -340,6 +625,7 @@ impl<T: Scannable, S: Scratch> BlockScanner<T, S> for RawStream {
 pub mod tests {
     extern crate env_logger;
 
+    use std::panic::{self, AssertUnwindSafe};
     use std::ptr;
 
     use super::super::*;
@@ -380,25 +666,86 @@ pub mod tests {
             .unwrap();
         let s = RawScratch::alloc(&db).unwrap();
 
-        db.scan::<BlockDatabase>("foo test bar", 0, &s, None, None).unwrap();
+        db.scan("foo test bar", 0, &s, |_| Matching::Continue).unwrap();
 
-        fn callback(id: u32, from: u64, to: u64, flags: u32, _: &BlockDatabase) -> u32 {
-            assert_eq!(id, 0);
-            assert_eq!(from, 4);
-            assert_eq!(to, 8);
-            assert_eq!(flags, 0);
+        let callback = |m: Match| {
+            assert_eq!(m.id, 0);
+            assert_eq!(m.from, 4);
+            assert_eq!(m.to, 8);
+            assert_eq!(m.flags, 0);
 
-            1
+            Matching::Terminate
         };
 
         assert_eq!(
-            db.scan("foo test bar".as_bytes(), 0, &s, Some(callback), Some(&db))
-                .err()
-                .unwrap(),
+            db.scan("foo test bar".as_bytes(), 0, &s, callback).err().unwrap(),
             Error::ScanTerminated
         );
     }
 
+    #[test]
+    fn test_block_scan_collect_matches() {
+        let _ = env_logger::try_init();
+
+        let db: BlockDatabase = patterns!(["foo", "bar"], flags => HS_FLAG_SOM_LEFTMOST)
+            .build()
+            .unwrap();
+        let s = RawScratch::alloc(&db).unwrap();
+
+        let mut matches = vec![];
+
+        db.scan("foo test bar", 0, &s, |m: Match| {
+            matches.push((m.id, m.from, m.to));
+
+            Matching::Continue
+        })
+        .unwrap();
+
+        assert_eq!(matches, vec![(1, 0, 3), (2, 9, 12)]);
+    }
+
+    #[test]
+    fn test_block_scan_panic() {
+        let _ = env_logger::try_init();
+
+        let db: BlockDatabase = pattern! {"test"}.build().unwrap();
+        let s = RawScratch::alloc(&db).unwrap();
+
+        let mut matches = 0;
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            db.scan("test test", 0, &s, |_| {
+                matches += 1;
+
+                panic!("callback panicked")
+            })
+        }));
+
+        assert!(result.is_err());
+        assert_eq!(matches, 1);
+
+        db.scan("test", 0, &s, |_| Matching::Continue).unwrap();
+    }
+
+    #[test]
+    fn test_block_scan_scratch_in_use() {
+        let _ = env_logger::try_init();
+
+        let db: BlockDatabase = pattern! {"test"}.build().unwrap();
+        let s = RawScratch::alloc(&db).unwrap();
+
+        db.scan("test", 0, &s, |_| {
+            let err = db.scan("test", 0, &s, |_| Matching::Continue).err().unwrap();
+
+            assert_eq!(err.kind(), &Error::ScratchInUse);
+            assert_eq!(err.operation(), Some(Operation::Scan));
+            assert_eq!(err.to_string(), "scan failed, The scratch region was already in use.");
+
+            Matching::Continue
+        })
+        .unwrap();
+    }
+
     #[test]
     fn test_vectored_scan() {
         let _ = env_logger::try_init();
@@ -410,23 +757,20 @@ pub mod tests {
 
         let data = vec!["foo", "test", "bar"];
 
-        db.scan::<VectoredDatabase>(&data, 0, &s, None, None).unwrap();
+        db.scan(&data, 0, &s, |_| Matching::Continue).unwrap();
 
-        fn callback(id: u32, from: u64, to: u64, flags: u32, _: &VectoredDatabase) -> u32 {
-            assert_eq!(id, 0);
-            assert_eq!(from, 3);
-            assert_eq!(to, 7);
-            assert_eq!(flags, 0);
+        let callback = |m: Match| {
+            assert_eq!(m.id, 0);
+            assert_eq!(m.from, 3);
+            assert_eq!(m.to, 7);
+            assert_eq!(m.flags, 0);
 
-            1
+            Matching::Terminate
         };
 
         let data = vec!["foo".as_bytes(), "test".as_bytes(), "bar".as_bytes()];
 
-        assert_eq!(
-            db.scan(&data, 0, &s, Some(callback), Some(&db)).err(),
-            Some(Error::ScanTerminated)
-        );
+        assert_eq!(db.scan(&data, 0, &s, callback).err(), Some(Error::ScanTerminated));
     }
 
     #[test]
@@ -440,19 +784,148 @@ pub mod tests {
 
         let data = vec!["foo", "test", "bar"];
 
-        fn callback(id: u32, from: u64, to: u64, flags: u32, _: &StreamingDatabase) -> u32 {
-            assert_eq!(id, 0);
-            assert_eq!(from, 0);
-            assert_eq!(to, 7);
-            assert_eq!(flags, 0);
+        let mut matches = 0;
+
+        {
+            let mut callback = |m: Match| {
+                assert_eq!(m.id, 0);
+                assert_eq!(m.from, 0);
+                assert_eq!(m.to, 7);
+                assert_eq!(m.flags, 0);
 
-            0
+                matches += 1;
+
+                Matching::Continue
+            };
+
+            for d in data {
+                st.scan(d, 0, &s, &mut callback).unwrap();
+            }
+
+            st.close(&s, &mut callback).unwrap();
         }
 
-        for d in data {
-            st.scan(d, 0, &s, Some(callback), Some(&db)).unwrap();
+        assert_eq!(matches, 1);
+    }
+
+    #[test]
+    fn test_streaming_compress() {
+        let _ = env_logger::try_init();
+
+        let db: StreamingDatabase = pattern! {"foobar"}.build().unwrap();
+
+        let s = RawScratch::alloc(&db).unwrap();
+        let st = db.open_stream(0).unwrap();
+
+        st.scan("foo", 0, &s, |_| Matching::Continue).unwrap();
+
+        let buf = st.compress().unwrap();
+
+        assert!(!buf.is_empty());
+
+        let mut reused = Vec::with_capacity(1);
+
+        assert_eq!(st.compress_into(&mut reused).unwrap(), buf.len());
+        assert_eq!(reused, buf);
+
+        drop(st);
+
+        let mut matches = vec![];
+
+        {
+            let st = unsafe { RawStream::expand(&db, &buf) }.unwrap();
+
+            st.scan("bar", 0, &s, |m: Match| {
+                matches.push(m.to);
+
+                Matching::Continue
+            })
+            .unwrap();
+
+            let st2 = db.open_stream(0).unwrap();
+
+            unsafe { st2.reset_and_expand(&buf, &s, |_| Matching::Continue) }.unwrap();
+
+            st2.scan("bar", 0, &s, |m: Match| {
+                matches.push(m.to);
+
+                Matching::Continue
+            })
+            .unwrap();
         }
 
-        st.close(&s, Some(callback), Some(&db)).unwrap();
+        assert_eq!(matches, vec![6, 6]);
+    }
+
+    #[test]
+    fn test_streaming_scan_chunks() {
+        let _ = env_logger::try_init();
+
+        let db: StreamingDatabase = pattern! {"test", flags => HS_FLAG_SOM_LEFTMOST}.build().unwrap();
+
+        let s = RawScratch::alloc(&db).unwrap();
+        let st = db.open_stream(0).unwrap();
+
+        let mut matches = vec![];
+
+        st.scan_chunks(b"foo test bar test", 3, 0, &s, |m: Match| {
+            matches.push((m.from, m.to));
+
+            Matching::Continue
+        })
+        .unwrap();
+
+        assert_eq!(matches, vec![(4, 8), (13, 17)]);
+    }
+
+    #[test]
+    fn test_vectored_scan_segments() {
+        let _ = env_logger::try_init();
+
+        let db: VectoredDatabase = pattern! {"test", flags => HS_FLAG_SOM_LEFTMOST}.build().unwrap();
+        let s = RawScratch::alloc(&db).unwrap();
+
+        let data = vec!["foo te", "", "st bar"];
+        let mut matches = vec![];
+
+        db.scan_segments(&data, 2, 0, &s, |m: Match| {
+            matches.push((m.from, m.to));
+
+            Matching::Continue
+        })
+        .unwrap();
+
+        assert_eq!(matches, vec![(4, 8)]);
+    }
+
+    #[test]
+    fn test_streaming_drop() {
+        let _ = env_logger::try_init();
+
+        fn assert_send<T: Send>(_: &T) {}
+
+        let db: StreamingDatabase = pattern! {"test$"}.build().unwrap();
+
+        let s = RawScratch::alloc(&db).unwrap();
+        let st = db.open_stream(0).unwrap();
+
+        assert_send(&st);
+
+        st.scan("foo test", 0, &s, |_| Matching::Continue).unwrap();
+
+        let st2 = st.clone();
+
+        drop(st);
+
+        let mut matches = vec![];
+
+        st2.close(&s, |m: Match| {
+            matches.push(m.to);
+
+            Matching::Continue
+        })
+        .unwrap();
+
+        assert_eq!(matches, vec![8]);
     }
 }
@@ -0,0 +1,366 @@
+use std::fmt;
+use std::iter::Zip;
+use std::slice;
+use std::thread;
+
+use api::*;
+use common::{BlockDatabase, RawDatabase, StreamingDatabase, VectoredDatabase};
+use compile::Patterns;
+use errors::Error;
+use runtime::{RawScratch, RawStream};
+
+/// A composite pattern database which patterns are partitioned into shards.
+///
+/// The shards are compiled on separate threads, which is faster for huge pattern sets,
+/// and avoids a single database growing too large to compile.
+///
+/// The data is scanned with each shard in turn, using one scratch space per shard.
+/// The matches are reported with the original pattern ids, but they are only ordered within a shard.
+pub struct ShardedDatabase<T: Type> {
+    shards: Vec<RawDatabase<T>>,
+}
+
+impl<T: Type> fmt::Debug for ShardedDatabase<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ShardedDatabase<{}>{{shards: {}}}", T::name(), self.shards.len())
+    }
+}
+
+impl<T: Type + 'static> ShardedDatabase<T> {
+    /// Partition the patterns into shards, and compile them on separate threads.
+    pub fn build(patterns: &Patterns, shards: usize) -> Result<ShardedDatabase<T>, Error> {
+        Self::build_for_platform(patterns, shards, &PlatformInfo::null())
+    }
+
+    /// Partition the patterns into shards, and compile them for the platform on separate threads.
+    ///
+    /// The patterns are assigned to the shards in turn, so the shards have a similar size.
+    pub fn build_for_platform(
+        patterns: &Patterns,
+        shards: usize,
+        platform: &PlatformInfo,
+    ) -> Result<ShardedDatabase<T>, Error> {
+        let shards = shards.max(1).min(patterns.len().max(1));
+        let target = if platform.as_ptr().is_null() {
+            None
+        } else {
+            Some((platform.tune(), platform.cpu_features()))
+        };
+
+        let threads = (0..shards)
+            .map(|shard| {
+                let patterns: Patterns = patterns.iter().skip(shard).step_by(shards).cloned().collect();
+
+                thread::spawn(move || {
+                    let platform = match target {
+                        Some((tune, cpu_features)) => PlatformInfo::new(tune, cpu_features),
+                        None => PlatformInfo::null(),
+                    };
+
+                    patterns.build_for_platform(&platform)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut dbs = Vec::with_capacity(shards);
+        let mut first_err = None;
+
+        for (shard, t) in threads.into_iter().enumerate() {
+            match t.join().expect("compile thread panicked") {
+                Ok(db) => dbs.push(db),
+                Err(Error::ExpressionError(mut err)) => {
+                    // map the index in the shard back to the index in the patterns
+                    err.index = err.index * shards + shard;
+
+                    first_err = first_err.or(Some(Error::ExpressionError(err)));
+                }
+                Err(err) => first_err = first_err.or(Some(err)),
+            }
+        }
+
+        if let Some(err) = first_err {
+            return Err(err);
+        }
+
+        debug!(
+            "compiled {} patterns to {} {} database shards",
+            patterns.len(),
+            shards,
+            T::name()
+        );
+
+        Ok(ShardedDatabase { shards: dbs })
+    }
+}
+
+impl<T: Type> ShardedDatabase<T> {
+    /// The database shards.
+    pub fn shards(&self) -> &[RawDatabase<T>] {
+        &self.shards
+    }
+
+    /// Allocate a scratch space for each shard.
+    pub fn alloc(&self) -> Result<ShardedScratch, Error> {
+        let mut scratches = Vec::with_capacity(self.shards.len());
+
+        for db in &self.shards {
+            scratches.push(try!(RawScratch::alloc(db)));
+        }
+
+        Ok(ShardedScratch { scratches })
+    }
+
+    /// Provides the total size of the database shards in bytes.
+    pub fn database_size(&self) -> Result<usize, Error> {
+        let mut size = 0;
+
+        for db in &self.shards {
+            size += try!(db.database_size());
+        }
+
+        Ok(size)
+    }
+}
+
+impl ShardedDatabase<Block> {
+    /// Scan the data with each shard in turn.
+    pub fn scan<D, F>(
+        &self,
+        data: D,
+        flags: ScanFlags,
+        scratch: &ShardedScratch,
+        mut on_match: F,
+    ) -> Result<&Self, Error>
+    where
+        D: Scannable,
+        F: MatchEventHandler,
+    {
+        let bytes = data.as_bytes();
+
+        for (db, s) in try!(scratch.zip(&self.shards)) {
+            try!(BlockDatabase::scan(db, bytes, flags, s, &mut on_match));
+        }
+
+        Ok(self)
+    }
+}
+
+impl ShardedDatabase<Vectored> {
+    /// Scan the data segments with each shard in turn.
+    pub fn scan<D, F>(
+        &self,
+        data: &[D],
+        flags: ScanFlags,
+        scratch: &ShardedScratch,
+        mut on_match: F,
+    ) -> Result<&Self, Error>
+    where
+        D: Scannable,
+        F: MatchEventHandler,
+    {
+        for (db, s) in try!(scratch.zip(&self.shards)) {
+            try!(VectoredDatabase::scan(db, data, flags, s, &mut on_match));
+        }
+
+        Ok(self)
+    }
+}
+
+impl ShardedDatabase<Streaming> {
+    /// Open a stream for each shard.
+    pub fn open_stream(&self, flags: StreamFlags) -> Result<ShardedStream<'_>, Error> {
+        let mut streams = Vec::with_capacity(self.shards.len());
+
+        for db in &self.shards {
+            streams.push(try!(StreamingDatabase::open_stream(db, flags)));
+        }
+
+        Ok(ShardedStream { streams })
+    }
+}
+
+/// The scratch spaces of a sharded database, one per shard.
+#[derive(Debug)]
+pub struct ShardedScratch {
+    scratches: Vec<RawScratch>,
+}
+
+impl ShardedScratch {
+    /// The scratch spaces of the shards.
+    pub fn scratches(&self) -> &[RawScratch] {
+        &self.scratches
+    }
+
+    /// Pair the shards with their scratch spaces, which must be allocated for the same number of shards.
+    fn zip<I>(&self, shards: I) -> Result<Zip<I::IntoIter, slice::Iter<'_, RawScratch>>, Error>
+    where
+        I: IntoIterator,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let shards = shards.into_iter();
+
+        if shards.len() != self.scratches.len() {
+            return Err(Error::Invalid);
+        }
+
+        Ok(shards.zip(self.scratches.iter()))
+    }
+}
+
+/// The streams of a sharded streaming database, one per shard.
+#[derive(Debug)]
+pub struct ShardedStream<'a> {
+    streams: Vec<RawStream<'a>>,
+}
+
+impl<'a> ShardedStream<'a> {
+    /// Write data to the stream of each shard.
+    pub fn scan<D, F>(
+        &self,
+        data: D,
+        flags: ScanFlags,
+        scratch: &ShardedScratch,
+        mut on_match: F,
+    ) -> Result<&Self, Error>
+    where
+        D: Scannable,
+        F: MatchEventHandler,
+    {
+        let bytes = data.as_bytes();
+
+        for (st, s) in try!(scratch.zip(&self.streams)) {
+            try!(st.scan(bytes, flags, s, &mut on_match));
+        }
+
+        Ok(self)
+    }
+
+    /// Close the stream of each shard, reporting any matches at end of data (EOD).
+    pub fn close<F>(self, scratch: &ShardedScratch, mut on_match: F) -> Result<(), Error>
+    where
+        F: MatchEventHandler,
+    {
+        for (st, s) in try!(scratch.zip(self.streams)) {
+            try!(st.close(s, &mut on_match));
+        }
+
+        Ok(())
+    }
+
+    /// Reset the stream of each shard to an initial state, reporting any matches at end of data (EOD).
+    pub fn reset<F>(&self, flags: StreamFlags, scratch: &ShardedScratch, mut on_match: F) -> Result<&Self, Error>
+    where
+        F: MatchEventHandler,
+    {
+        for (st, s) in try!(scratch.zip(&self.streams)) {
+            try!(st.reset(flags, s, &mut on_match));
+        }
+
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    extern crate env_logger;
+
+    use super::super::*;
+
+    #[test]
+    fn test_sharded_block_database() {
+        let _ = env_logger::try_init();
+
+        let patterns = patterns!(["foo", "bar", "baz", "qux", "test"]);
+        let db: ShardedDatabase<Block> = ShardedDatabase::build(&patterns, 2).unwrap();
+
+        assert_eq!(db.shards().len(), 2);
+        assert!(db.database_size().unwrap() > 0);
+
+        let s = db.alloc().unwrap();
+        let mut matches = vec![];
+
+        db.scan("foo test qux", 0, &s, |m: Match| {
+            matches.push((m.id, m.to));
+
+            Matching::Continue
+        })
+        .unwrap();
+
+        matches.sort();
+
+        assert_eq!(matches, vec![(1, 3), (4, 12), (5, 8)]);
+
+        let mut count = 0;
+
+        assert_eq!(
+            db.scan("foo test qux", 0, &s, |_| {
+                count += 1;
+
+                Matching::Terminate
+            })
+            .unwrap_err(),
+            Error::ScanTerminated
+        );
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn test_sharded_scratch_mismatch() {
+        let _ = env_logger::try_init();
+
+        let patterns = patterns!(["foo", "bar", "baz"]);
+        let db: ShardedDatabase<Block> = ShardedDatabase::build(&patterns, 3).unwrap();
+        let other: ShardedDatabase<Block> = ShardedDatabase::build(&patterns, 2).unwrap();
+        let s = other.alloc().unwrap();
+
+        assert_eq!(
+            db.scan("foo", 0, &s, |_| Matching::Continue).unwrap_err(),
+            Error::Invalid
+        );
+    }
+
+    #[test]
+    fn test_sharded_database_error() {
+        let _ = env_logger::try_init();
+
+        let patterns = patterns!(["foo", "bar", "baz", "qux("]);
+
+        match ShardedDatabase::<Block>::build(&patterns, 3) {
+            Err(Error::ExpressionError(err)) => {
+                assert_eq!(err.index, 3);
+                assert_eq!(err.id, Some(4));
+            }
+            res => panic!("unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_sharded_streaming_database() {
+        let _ = env_logger::try_init();
+
+        let patterns = patterns!(["foobar", "end$"]);
+        let db: ShardedDatabase<Streaming> = ShardedDatabase::build(&patterns, 4).unwrap();
+
+        assert_eq!(db.shards().len(), 2);
+
+        let s = db.alloc().unwrap();
+        let st = db.open_stream(0).unwrap();
+        let mut matches = vec![];
+
+        {
+            let mut on_match = |m: Match| {
+                matches.push((m.id, m.to));
+
+                Matching::Continue
+            };
+
+            st.scan("foo", 0, &s, &mut on_match).unwrap();
+            st.scan("bar, the end", 0, &s, &mut on_match).unwrap();
+            st.close(&s, &mut on_match).unwrap();
+        }
+
+        matches.sort();
+
+        assert_eq!(matches, vec![(1, 6), (2, 15)]);
+    }
+}